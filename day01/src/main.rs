@@ -2,44 +2,109 @@ use std::env;
 use std::fs::File;
 use std::io::{self, BufRead};
 
-trait CountIncreases {
+trait CountIncreases<T> {
     fn count_increases(&self) -> usize;
+
+    fn increase_indices(&self) -> Vec<usize>;
+
+    fn count_sliding_window_increases(&self, window: usize) -> usize
+    where
+        T: Copy + Into<i128>;
+
+    fn count_median_window_increases(&self, window: usize) -> usize
+    where
+        T: Ord + Copy;
 }
 
-impl CountIncreases for [u16] {
+impl<T: PartialOrd> CountIncreases<T> for [T] {
     fn count_increases(&self) -> usize {
-        self.windows(2).fold(0, |count, xs| {
-            let last_x = xs[0];
-            let curr_x = xs[1];
+        self.increase_indices().len()
+    }
+
+    /// Returns the index of the later element of each increasing pair.
+    fn increase_indices(&self) -> Vec<usize> {
+        self.windows(2)
+            .enumerate()
+            .filter_map(|(idx, xs)| {
+                let last_x = &xs[0];
+                let curr_x = &xs[1];
 
-            if curr_x > last_x {
-                count + 1
-            } else {
-                count
-            }
-        })
+                if curr_x > last_x {
+                    Some(idx + 1)
+                } else {
+                    None
+                }
+            })
+            .collect()
     }
-}
 
-/// CLI usage: cargo run -- input.txt
-fn main() {
-    let filename = env::args().nth(1).expect("Missing input file");
+    /// Sums each window into `i128` so that the sums can't overflow for
+    /// any primitive integer type up to 64 bits. Window size 0 and slices
+    /// shorter than `window` have no increases.
+    fn count_sliding_window_increases(&self, window: usize) -> usize
+    where
+        T: Copy + Into<i128>,
+    {
+        if window == 0 {
+            return 0;
+        }
+
+        let sums: Vec<i128> = self
+            .windows(window)
+            .map(|xs| xs.iter().map(|&x| x.into()).sum())
+            .collect();
+        sums.count_increases()
+    }
+
+    /// Compares the medians of sliding windows instead of sums. For even
+    /// window sizes, the lower median is used.
+    fn count_median_window_increases(&self, window: usize) -> usize
+    where
+        T: Ord + Copy,
+    {
+        if window == 0 {
+            return 0;
+        }
 
-    let lines: Vec<u16> = io::BufReader::new(File::open(filename).expect("File not found"))
+        let medians: Vec<T> = self
+            .windows(window)
+            .map(|xs| {
+                let mut sorted = xs.to_vec();
+                sorted.sort();
+                sorted[(window - 1) / 2]
+            })
+            .collect();
+        medians.count_increases()
+    }
+}
+
+fn read_depths(reader: impl BufRead) -> Vec<u16> {
+    reader
         .lines()
         .map(|l| l.expect("Line not UTF-8").parse().expect("Line not u16"))
-        .collect();
+        .collect()
+}
+
+/// CLI usage: cargo run -- input.txt, or: cat input.txt | cargo run
+fn main() {
+    let lines = match env::args().nth(1) {
+        Some(filename) => read_depths(io::BufReader::new(
+            File::open(filename).expect("File not found"),
+        )),
+        None => read_depths(io::stdin().lock()),
+    };
 
     let count_increases_by_groups1 = lines.count_increases();
 
     println!("count_increases_by_groups1={}", count_increases_by_groups1);
 
-    let count_increases_by_groups3 = {
-        let sum_of_groups3: Vec<u16> = lines.windows(3).map(|xs| xs[0] + xs[1] + xs[2]).collect();
-        sum_of_groups3.count_increases()
-    };
+    let count_increases_by_groups3 = lines.count_sliding_window_increases(3);
 
     println!("count_increases_by_groups3={}", count_increases_by_groups3);
+
+    let count_increases_by_medians3 = lines.count_median_window_increases(3);
+
+    println!("count_increases_by_medians3={}", count_increases_by_medians3);
 }
 
 #[cfg(test)]
@@ -48,11 +113,55 @@ mod tests {
 
     #[test]
     fn test_empty() {
-        assert_eq!([].count_increases(), 0);
+        assert_eq!(([] as [u16; 0]).count_increases(), 0);
     }
 
     #[test]
     fn test_nonempty() {
         assert_eq!([42, 41, 43, 40, 41, 45].count_increases(), 3);
     }
+
+    #[test]
+    fn test_increase_indices() {
+        assert_eq!([42, 41, 43, 40, 41, 45].increase_indices(), vec![2, 4, 5]);
+        assert_eq!(([] as [u16; 0]).increase_indices(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_read_depths() {
+        assert_eq!(
+            read_depths("199\n200\n208\n".as_bytes()),
+            vec![199, 200, 208]
+        );
+    }
+
+    #[test]
+    fn test_u32() {
+        assert_eq!([70000u32, 69999, 70001, 65536, 65537].count_increases(), 2);
+    }
+
+    const EXAMPLE_DEPTHS: [u16; 10] = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+
+    #[test]
+    fn test_sliding_window_sizes() {
+        assert_eq!(EXAMPLE_DEPTHS.count_sliding_window_increases(1), 7);
+        assert_eq!(EXAMPLE_DEPTHS.count_sliding_window_increases(2), 5);
+        assert_eq!(EXAMPLE_DEPTHS.count_sliding_window_increases(3), 5);
+    }
+
+    #[test]
+    fn test_sliding_window_degenerate() {
+        assert_eq!(EXAMPLE_DEPTHS.count_sliding_window_increases(0), 0);
+        assert_eq!(EXAMPLE_DEPTHS.count_sliding_window_increases(11), 0);
+    }
+
+    #[test]
+    fn test_median_window() {
+        let xs = [1, 5, 2, 8, 3, 9, 4];
+        assert_eq!(xs.count_median_window_increases(1), 3);
+        assert_eq!(xs.count_median_window_increases(2), 3);
+        assert_eq!(xs.count_median_window_increases(3), 2);
+        assert_eq!(xs.count_median_window_increases(0), 0);
+        assert_eq!(xs.count_median_window_increases(8), 0);
+    }
 }
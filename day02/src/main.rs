@@ -4,21 +4,43 @@ use nom::character::complete::{i32, space1};
 use nom::combinator::value;
 use nom::sequence::separated_pair;
 use nom::{Finish, IResult};
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::process;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum Direction {
     Up,
     Down,
     Forward,
+    Left,
+    Right,
 }
 
 #[derive(Debug, PartialEq)]
 struct Movement {
     dx: i32,
     dy: i32,
+    dz: i32,
+}
+
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line_num: usize,
+    line: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unknown movement at line {}: {}",
+            self.line_num, self.line
+        )
+    }
 }
 
 fn parse_direction(input: &str) -> IResult<&str, Direction> {
@@ -27,51 +49,176 @@ fn parse_direction(input: &str) -> IResult<&str, Direction> {
         value(Up, tag("up")),
         value(Down, tag("down")),
         value(Forward, tag("forward")),
+        value(Left, tag("left")),
+        value(Right, tag("right")),
     ))(input)
 }
 
-fn parse_movement(input: &str) -> IResult<&str, Movement> {
-    use Direction::*;
+impl Movement {
+    fn new(direction: &Direction, delta: i32) -> Movement {
+        use Direction::*;
+        match direction {
+            Up => Movement {
+                dx: 0,
+                dy: -delta,
+                dz: 0,
+            },
+            Down => Movement {
+                dx: 0,
+                dy: delta,
+                dz: 0,
+            },
+            Forward => Movement {
+                dx: delta,
+                dy: 0,
+                dz: 0,
+            },
+            Left => Movement {
+                dx: 0,
+                dy: 0,
+                dz: -delta,
+            },
+            Right => Movement {
+                dx: 0,
+                dy: 0,
+                dz: delta,
+            },
+        }
+    }
+}
+
+fn parse_directed_movement(input: &str) -> IResult<&str, (Direction, Movement)> {
     let (unconsumed, (direction, delta)) = separated_pair(parse_direction, space1, i32)(input)?;
-    let movement = match direction {
-        Up => Movement { dx: 0, dy: -delta },
-        Down => Movement { dx: 0, dy: delta },
-        Forward => Movement { dx: delta, dy: 0 },
-    };
+    let movement = Movement::new(&direction, delta);
+    Ok((unconsumed, (direction, movement)))
+}
+
+#[cfg(test)]
+fn parse_movement(input: &str) -> IResult<&str, Movement> {
+    let (unconsumed, (_, movement)) = parse_directed_movement(input)?;
     Ok((unconsumed, movement))
 }
 
+/// Parses a line as a direction and the movement it makes. On failure, the
+/// error tells the 1-based line number and contents of the malformed line.
+fn parse_movement_line(line_num: usize, line: String) -> Result<(Direction, Movement), ParseError> {
+    match parse_directed_movement(&line).finish() {
+        Ok((_, dm)) => Ok(dm),
+        Err(_) => Err(ParseError { line_num, line }),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Navigator {
+    x: i32,
+    y: i32,
+    z: i32,
+    aim: i32,
+}
+
+impl Navigator {
+    fn new() -> Self {
+        Default::default()
+    }
+
+    fn apply_direct(&mut self, m: &Movement) {
+        self.x += m.dx;
+        self.y += m.dy;
+        self.z += m.dz;
+    }
+
+    /// Vertical movement changes the aim instead of the position. Lateral
+    /// movement doesn't affect the aim.
+    fn apply_aimed(&mut self, m: &Movement) {
+        self.x += m.dx;
+        self.y += self.aim * m.dx;
+        self.z += m.dz;
+        self.aim += m.dy;
+    }
+
+    fn x(&self) -> i32 {
+        self.x
+    }
+
+    fn y(&self) -> i32 {
+        self.y
+    }
+
+    fn z(&self) -> i32 {
+        self.z
+    }
+}
+
+#[derive(Debug)]
+enum CourseError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for CourseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CourseError::*;
+        match *self {
+            Io(ref err) => write!(f, "Cannot read course: {}", err),
+            Parse(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CourseResult {
+    direct: Navigator,
+    aimed: Navigator,
+    /// How many times each direction appears in the course
+    direction_counts: HashMap<Direction, usize>,
+}
+
+/// Applies each movement of the course with both the direct and aimed
+/// interpretations.
+fn run_course(reader: impl BufRead) -> Result<CourseResult, CourseError> {
+    let mut result = CourseResult {
+        direct: Navigator::new(),
+        aimed: Navigator::new(),
+        direction_counts: HashMap::new(),
+    };
+
+    for (idx, l) in reader.lines().enumerate() {
+        let line = l.map_err(CourseError::Io)?;
+
+        let (direction, movement) =
+            parse_movement_line(idx + 1, line).map_err(CourseError::Parse)?;
+
+        result.direct.apply_direct(&movement);
+        result.aimed.apply_aimed(&movement);
+
+        *result.direction_counts.entry(direction).or_insert(0) += 1;
+    }
+
+    Ok(result)
+}
+
 /// CLI usage: cargo run -- input.txt
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
-    let movements: Vec<Movement> =
-        io::BufReader::new(File::open(filename).expect("File not found"))
-            .lines()
-            .map(|l| {
-                parse_movement(&l.expect("Line not UTF-8"))
-                    .finish()
-                    .expect("Unknown movement")
-                    .1
-            })
-            .collect();
+    let CourseResult { direct, aimed, .. } = match run_course(io::BufReader::new(
+        File::open(filename).expect("File not found"),
+    )) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
 
-    let pos_direct = movements.iter().fold((0, 0), |(pos_x, pos_y), mov| {
-        (pos_x + mov.dx, pos_y + mov.dy)
-    });
+    println!("pos_direct (x * y): {}", direct.x() * direct.y());
 
-    println!("pos_direct (x * y): {}", pos_direct.0 * pos_direct.1);
+    println!("pos_aimed (x * y): {}", aimed.x() * aimed.y());
 
-    let pos_aimed = movements
-        .iter()
-        .fold((0, 0, 0), |(pos_x, pos_y, aim), mov| {
-            let pos_x_new = pos_x + mov.dx;
-            let pos_y_new = pos_y + aim * mov.dx;
-            let aim_new = aim + mov.dy;
-            (pos_x_new, pos_y_new, aim_new)
-        });
-
-    println!("pos_aimed (x * y): {}", pos_aimed.0 * pos_aimed.1);
+    println!(
+        "pos_aimed (x * y * z): {}",
+        aimed.x() as i64 * aimed.y() as i64 * aimed.z() as i64
+    );
 }
 
 #[cfg(test)]
@@ -81,6 +228,99 @@ mod tests {
     #[test]
     fn parse_movement_down() {
         let (_, m) = parse_movement("down 42").unwrap();
-        assert_eq!(m, Movement { dx: 0, dy: 42 });
+        assert_eq!(
+            m,
+            Movement {
+                dx: 0,
+                dy: 42,
+                dz: 0
+            }
+        );
+    }
+
+    #[test]
+    fn parse_movement_left() {
+        let (_, m) = parse_movement("left 5").unwrap();
+        assert_eq!(
+            m,
+            Movement {
+                dx: 0,
+                dy: 0,
+                dz: -5
+            }
+        );
+    }
+
+    fn example_movements() -> Vec<Movement> {
+        [
+            "forward 5",
+            "down 5",
+            "forward 8",
+            "up 3",
+            "down 8",
+            "forward 2",
+        ]
+        .iter()
+        .map(|l| parse_movement(l).unwrap().1)
+        .collect()
+    }
+
+    #[test]
+    fn navigator_direct() {
+        let mut nav = Navigator::new();
+        for m in &example_movements() {
+            nav.apply_direct(m);
+        }
+        assert_eq!(nav.x() * nav.y(), 150);
+    }
+
+    #[test]
+    fn navigator_aimed() {
+        let mut nav = Navigator::new();
+        for m in &example_movements() {
+            nav.apply_aimed(m);
+        }
+        assert_eq!(nav.x() * nav.y(), 900);
+    }
+
+    #[test]
+    fn parse_movement_line_invalid() {
+        assert_eq!(
+            parse_movement_line(3, "sideways 3".into()).unwrap_err(),
+            ParseError {
+                line_num: 3,
+                line: "sideways 3".into()
+            }
+        );
+    }
+
+    #[test]
+    fn run_course_with_example() {
+        let input = "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2\n";
+        let result = run_course(input.as_bytes()).unwrap();
+        assert_eq!(result.direct.x() * result.direct.y(), 150);
+        assert_eq!(result.aimed.x() * result.aimed.y(), 900);
+        assert_eq!(
+            result.direction_counts,
+            HashMap::from([
+                (Direction::Forward, 3),
+                (Direction::Down, 2),
+                (Direction::Up, 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn run_course_invalid_line() {
+        match run_course("forward 5\ndown 5\nsideways 3\nup 3\n".as_bytes()) {
+            Err(CourseError::Parse(err)) => assert_eq!(
+                err,
+                ParseError {
+                    line_num: 3,
+                    line: "sideways 3".into()
+                }
+            ),
+            res => panic!("Unexpected result: {:?}", res),
+        }
     }
 }
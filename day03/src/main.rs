@@ -1,25 +1,62 @@
 use bitvec::field::BitField;
 use bitvec::prelude as bv;
+use std::cmp::Ordering;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 
-const DIAGNOSTIC_BIT_LEN: usize = 12;
+#[derive(Debug, PartialEq)]
+enum RatingError {
+    NoCandidates,
+    Ambiguous(usize),
+}
+
+impl fmt::Display for RatingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RatingError::*;
+        match *self {
+            NoCandidates => write!(f, "No candidates left for rating"),
+            Ambiguous(n) => write!(f, "Ambiguous rating ({} candidates left)", n),
+        }
+    }
+}
 
-type DiagnosticsBitArray = bv::BitArr!(for DIAGNOSTIC_BIT_LEN);
+/// Bit at index 0 is the least significant bit.
+type DiagnosticsBitVec = bv::BitVec;
 
 #[derive(Debug)]
 enum Error {
     InvalidDiagnosticLineLength(usize),
     InvalidDiagnosticLineContents(String),
+    UnexpectedDiagnosticLineLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match *self {
+            InvalidDiagnosticLineLength(len) => {
+                write!(f, "Invalid diagnostic line length ({})", len)
+            }
+            InvalidDiagnosticLineContents(ref line) => {
+                write!(f, "Invalid diagnostic line contents: {}", line)
+            }
+            UnexpectedDiagnosticLineLength { expected, actual } => write!(
+                f,
+                "Unexpected diagnostic line length (should be {}, but was {})",
+                expected, actual
+            ),
+        }
+    }
 }
 
-fn parse_diagnostics_line(line: &str) -> Result<DiagnosticsBitArray, Error> {
-    if line.len() != 12 {
+fn parse_diagnostics_line(line: &str) -> Result<DiagnosticsBitVec, Error> {
+    if line.is_empty() || line.len() > usize::BITS as usize {
         return Err(Error::InvalidDiagnosticLineLength(line.len()));
     }
 
-    let mut arr: DiagnosticsBitArray = bv::BitArray::ZERO;
+    let mut arr = DiagnosticsBitVec::repeat(false, line.len());
 
     for (i, c) in line.chars().rev().enumerate() {
         let b = match c {
@@ -34,13 +71,71 @@ fn parse_diagnostics_line(line: &str) -> Result<DiagnosticsBitArray, Error> {
     Ok(arr)
 }
 
-fn read_gamma_and_epsilon(diagnostics: &[DiagnosticsBitArray]) -> (usize, usize) {
-    let mut gamma: DiagnosticsBitArray = bv::BitArray::ZERO;
-    let mut epsilon: DiagnosticsBitArray = bv::BitArray::ZERO;
+/// Parses diagnostics lines, requiring that all of them have the same width
+/// as the first line.
+fn parse_diagnostics<I, S>(lines: I) -> Result<Vec<DiagnosticsBitVec>, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut diagnostics: Vec<DiagnosticsBitVec> = vec![];
+
+    for line in lines {
+        let arr = parse_diagnostics_line(line.as_ref())?;
+
+        if let Some(first) = diagnostics.first() {
+            if first.len() != arr.len() {
+                return Err(Error::UnexpectedDiagnosticLineLength {
+                    expected: first.len(),
+                    actual: arr.len(),
+                });
+            }
+        }
+
+        diagnostics.push(arr);
+    }
+
+    Ok(diagnostics)
+}
+
+fn diagnostics_width(diagnostics: &[DiagnosticsBitVec]) -> usize {
+    diagnostics.first().map_or(0, |d| d.len())
+}
+
+/// Which bit counts as the most common one when there are equally many ones
+/// and zeros.
+#[derive(Clone, Copy, Debug)]
+enum TieBreak {
+    PreferOne,
+    PreferZero,
+}
+
+fn most_common_bit(num_ones: usize, num_bits: usize, tie_break: TieBreak) -> bool {
+    let num_zeros = num_bits - num_ones;
+    match num_ones.cmp(&num_zeros) {
+        Ordering::Greater => true,
+        Ordering::Less => false,
+        Ordering::Equal => matches!(tie_break, TieBreak::PreferOne),
+    }
+}
+
+/// Epsilon is the bitwise complement of gamma, so `tie_break` affects both.
+fn read_gamma_and_epsilon(
+    diagnostics: &[DiagnosticsBitVec],
+    tie_break: TieBreak,
+) -> (usize, usize) {
+    let width = diagnostics_width(diagnostics);
+
+    if width == 0 {
+        return (0, 0);
+    }
 
-    for i in 0..DIAGNOSTIC_BIT_LEN {
+    let mut gamma = DiagnosticsBitVec::repeat(false, width);
+    let mut epsilon = DiagnosticsBitVec::repeat(false, width);
+
+    for i in 0..width {
         let num_ones = diagnostics.iter().filter(|d| *d.get(i).unwrap()).count();
-        let most_common_bit = num_ones > (diagnostics.len() - num_ones);
+        let most_common_bit = most_common_bit(num_ones, diagnostics.len(), tie_break);
         gamma.set(i, most_common_bit);
         epsilon.set(i, !most_common_bit);
     }
@@ -51,58 +146,120 @@ fn read_gamma_and_epsilon(diagnostics: &[DiagnosticsBitArray]) -> (usize, usize)
     )
 }
 
-fn read_filtered_rating<S>(diagnostics: &[DiagnosticsBitArray], mut select_bit: S) -> usize
+/// Filters diagnostics bit by bit, starting from the most significant bit.
+/// `select_bit` receives the most common bit of the remaining candidates
+/// (resolved with `tie_break`) and returns the bit to keep.
+fn read_filtered_rating<S>(
+    diagnostics: &[DiagnosticsBitVec],
+    tie_break: TieBreak,
+    select_bit: S,
+) -> Result<usize, RatingError>
+where
+    S: FnMut(bool) -> bool,
+{
+    let filtered = filter_rating_candidates(diagnostics, tie_break, select_bit, |_, _| ());
+
+    match filtered.len() {
+        0 => Err(RatingError::NoCandidates),
+        1 => Ok(filtered[0].as_bitslice().load::<usize>()),
+        n => Err(RatingError::Ambiguous(n)),
+    }
+}
+
+/// Like `read_filtered_rating`, but returns the selected bit (0 or 1) and the
+/// number of remaining candidates after processing each bit position.
+fn filter_rating_steps<S>(
+    diagnostics: &[DiagnosticsBitVec],
+    tie_break: TieBreak,
+    select_bit: S,
+) -> Vec<(usize, usize)>
+where
+    S: FnMut(bool) -> bool,
+{
+    let mut steps = vec![];
+
+    filter_rating_candidates(diagnostics, tie_break, select_bit, |bit, num_candidates| {
+        steps.push((bit as usize, num_candidates))
+    });
+
+    steps
+}
+
+fn filter_rating_candidates<S, O>(
+    diagnostics: &[DiagnosticsBitVec],
+    tie_break: TieBreak,
+    mut select_bit: S,
+    mut observe_step: O,
+) -> Vec<DiagnosticsBitVec>
 where
-    S: FnMut(usize, usize) -> bool,
+    S: FnMut(bool) -> bool,
+    O: FnMut(bool, usize),
 {
     let mut filtered = diagnostics.to_vec();
-    let mut safe_idx = Some(DIAGNOSTIC_BIT_LEN - 1);
+    let mut safe_idx = diagnostics_width(diagnostics).checked_sub(1);
 
     while filtered.len() > 1 && safe_idx.is_some() {
         let i = safe_idx.unwrap();
 
         let num_ones = filtered.iter().filter(|d| *d.get(i).unwrap()).count();
-        let selected_bit = select_bit(num_ones, filtered.len());
+        let selected_bit = select_bit(most_common_bit(num_ones, filtered.len(), tie_break));
 
         filtered.retain(|d| *d.get(i).unwrap() == selected_bit);
 
+        observe_step(selected_bit, filtered.len());
+
         safe_idx = i.checked_sub(1);
     }
 
-    assert!(filtered.len() == 1, "Not found");
+    filtered
+}
+
+/// The product of gamma and epsilon rates
+fn power_consumption(diagnostics: &[DiagnosticsBitVec]) -> usize {
+    let (gamma, epsilon) = read_gamma_and_epsilon(diagnostics, TieBreak::PreferZero);
+    gamma * epsilon
+}
+
+/// The product of oxygen generator and CO2 scrubber ratings
+fn life_support_rating(diagnostics: &[DiagnosticsBitVec]) -> Result<usize, RatingError> {
+    let oxygen_generator_rating =
+        read_filtered_rating(diagnostics, TieBreak::PreferOne, |most_common| most_common)?;
 
-    filtered[0].as_bitslice().load::<usize>()
+    let co2_scrubber_rating =
+        read_filtered_rating(diagnostics, TieBreak::PreferOne, |most_common| !most_common)?;
+
+    Ok(oxygen_generator_rating * co2_scrubber_rating)
 }
 
 /// CLI usage: cargo run -- input.txt
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
-    let diagnostics: Vec<DiagnosticsBitArray> =
+    let diagnostics = parse_diagnostics(
         io::BufReader::new(File::open(filename).expect("File not found"))
             .lines()
-            .map(|l| {
-                parse_diagnostics_line(&l.expect("Line not UTF-8")).expect("Invalid diagnostics")
-            })
-            .collect();
-
-    let (gamma, epsilon) = read_gamma_and_epsilon(&diagnostics[..]);
+            .map(|l| l.expect("Line not UTF-8")),
+    )
+    .unwrap_or_else(|e| panic!("Invalid diagnostics: {}", e));
 
-    println!("power: {}", gamma * epsilon);
+    println!("power: {}", power_consumption(&diagnostics));
 
-    let oxygen_generator_rating = read_filtered_rating(&diagnostics[..], |num_ones, num_bits| {
-        num_ones >= (num_bits - num_ones)
-    });
+    println!(
+        "life support rating: {}",
+        life_support_rating(&diagnostics)
+            .unwrap_or_else(|e| panic!("Invalid life support rating: {}", e)),
+    );
 
-    let co2_scrubber_rating = read_filtered_rating(
-        &diagnostics[..],
-        #[allow(clippy::overflow_check_conditional)]
-        |num_ones, num_bits| num_ones < (num_bits - num_ones),
+    println!(
+        "oxygen generator rating steps (bit, candidates): {:?}",
+        filter_rating_steps(&diagnostics, TieBreak::PreferOne, |most_common| most_common)
     );
 
     println!(
-        "life support rating: {}",
-        oxygen_generator_rating * co2_scrubber_rating,
+        "CO2 scrubber rating steps (bit, candidates): {:?}",
+        filter_rating_steps(&diagnostics, TieBreak::PreferOne, |most_common| {
+            !most_common
+        })
     );
 }
 
@@ -117,7 +274,122 @@ mod tests {
         let arr = parse_diagnostics_line("110100000101").unwrap();
         assert_eq!(
             arr.as_bitslice(),
-            0b1101_0000_0101_usize.view_bits::<bv::Lsb0>()
+            0b1101_0000_0101_usize.view_bits::<bv::Lsb0>()[..12]
+        );
+    }
+
+    const EXAMPLE: [&str; 12] = [
+        "00100", "11110", "10110", "10111", "10101", "01111", "00111", "11100", "10000", "11001",
+        "00010", "01010",
+    ];
+
+    #[test]
+    fn parse_diagnostics_when_mixed_widths() {
+        assert!(matches!(
+            parse_diagnostics(["00100", "0111"]),
+            Err(Error::UnexpectedDiagnosticLineLength {
+                expected: 5,
+                actual: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn read_gamma_and_epsilon_with_5_bit_example() {
+        let diagnostics = parse_diagnostics(EXAMPLE).unwrap();
+        assert_eq!(
+            read_gamma_and_epsilon(&diagnostics, TieBreak::PreferZero),
+            (22, 9)
+        );
+    }
+
+    #[test]
+    fn read_filtered_rating_with_5_bit_example() {
+        let diagnostics = parse_diagnostics(EXAMPLE).unwrap();
+        assert_eq!(
+            read_filtered_rating(&diagnostics, TieBreak::PreferOne, |b| b),
+            Ok(23)
+        );
+        assert_eq!(
+            read_filtered_rating(&diagnostics, TieBreak::PreferOne, |b| !b),
+            Ok(10)
+        );
+    }
+
+    #[test]
+    fn read_gamma_and_epsilon_with_tie() {
+        let diagnostics = parse_diagnostics(["10", "11"]).unwrap();
+        assert_eq!(
+            read_gamma_and_epsilon(&diagnostics, TieBreak::PreferOne),
+            (0b11, 0b00)
+        );
+        assert_eq!(
+            read_gamma_and_epsilon(&diagnostics, TieBreak::PreferZero),
+            (0b10, 0b01)
+        );
+    }
+
+    #[test]
+    fn read_filtered_rating_with_tie() {
+        let diagnostics = parse_diagnostics(["01", "11"]).unwrap();
+        assert_eq!(
+            read_filtered_rating(&diagnostics, TieBreak::PreferOne, |b| b),
+            Ok(0b11)
+        );
+        assert_eq!(
+            read_filtered_rating(&diagnostics, TieBreak::PreferZero, |b| b),
+            Ok(0b01)
+        );
+        assert_eq!(
+            read_filtered_rating(&diagnostics, TieBreak::PreferOne, |b| !b),
+            Ok(0b01)
+        );
+        assert_eq!(
+            read_filtered_rating(&diagnostics, TieBreak::PreferZero, |b| !b),
+            Ok(0b11)
+        );
+    }
+
+    #[test]
+    fn read_filtered_rating_when_identical_rows() {
+        let diagnostics = parse_diagnostics(["101", "101"]).unwrap();
+        assert_eq!(
+            read_filtered_rating(&diagnostics, TieBreak::PreferOne, |b| b),
+            Err(RatingError::Ambiguous(2))
+        );
+    }
+
+    #[test]
+    fn read_filtered_rating_when_empty() {
+        assert_eq!(
+            read_filtered_rating(&[], TieBreak::PreferOne, |b| b),
+            Err(RatingError::NoCandidates)
+        );
+    }
+
+    #[test]
+    fn power_consumption_and_life_support_rating_with_5_bit_example() {
+        let diagnostics = parse_diagnostics(EXAMPLE).unwrap();
+        assert_eq!(power_consumption(&diagnostics), 198);
+        assert_eq!(life_support_rating(&diagnostics), Ok(230));
+    }
+
+    #[test]
+    fn read_gamma_and_epsilon_when_empty() {
+        assert_eq!(read_gamma_and_epsilon(&[], TieBreak::PreferZero), (0, 0));
+        assert_eq!(power_consumption(&[]), 0);
+    }
+
+    #[test]
+    fn filter_rating_steps_with_5_bit_example() {
+        let diagnostics = parse_diagnostics(EXAMPLE).unwrap();
+        assert_eq!(
+            filter_rating_steps(&diagnostics, TieBreak::PreferOne, |b| b),
+            vec![(1, 7), (0, 4), (1, 3), (1, 2), (1, 1)]
+        );
+        assert_eq!(
+            filter_rating_steps(&diagnostics, TieBreak::PreferOne, |b| !b),
+            vec![(0, 5), (1, 2), (0, 1)]
         );
     }
 }
@@ -1,8 +1,10 @@
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::mem::take;
 use std::ops::{Index, IndexMut};
+use std::process;
 
 fn parse_draws(line: &str) -> Vec<u8> {
     line.split(',')
@@ -16,6 +18,28 @@ const BINGO_ROWS: usize = 5;
 type BingoLine = [Option<u8>; BINGO_COLS];
 type BingoLines = [BingoLine; BINGO_ROWS];
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WinningLine {
+    Row(usize),
+    Col(usize),
+    /// From the top left corner to the bottom right corner
+    Diagonal,
+    /// From the top right corner to the bottom left corner
+    AntiDiagonal,
+}
+
+impl fmt::Display for WinningLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use WinningLine::*;
+        match *self {
+            Row(idx) => write!(f, "row {}", idx),
+            Col(idx) => write!(f, "col {}", idx),
+            Diagonal => write!(f, "diagonal"),
+            AntiDiagonal => write!(f, "anti-diagonal"),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct BingoBoard {
     rows: BingoLines,
@@ -46,7 +70,7 @@ impl BingoBoard {
         draw_hit
     }
 
-    fn numbers(&self) -> BingoNumbersIter {
+    fn numbers(&self) -> BingoNumbersIter<'_> {
         BingoNumbersIter {
             current_row: 0,
             current_col: 0,
@@ -58,15 +82,49 @@ impl BingoBoard {
         self.numbers().flatten().map(|n| n as u32).sum()
     }
 
-    fn has_bingo(&self) -> bool {
-        self.has_bingo_by_horizontal_line() || self.has_bingo_by_vertical_line()
+    /// The sum of unmarked numbers multiplied by the draw that won the board
+    fn score(&self, last_draw: u8) -> u32 {
+        self.numbers_sum() * last_draw as u32
+    }
+
+    fn winning_line(&self) -> Option<WinningLine> {
+        self.winning_horizontal_line()
+            .or_else(|| self.winning_vertical_line())
+    }
+
+    fn winning_line_with(&self, allow_diagonal: bool) -> Option<WinningLine> {
+        self.winning_line().or_else(|| {
+            if allow_diagonal {
+                self.winning_diagonal_line()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Checks both main diagonals. Only meaningful for square boards.
+    fn winning_diagonal_line(&self) -> Option<WinningLine> {
+        if BINGO_ROWS != BINGO_COLS {
+            return None;
+        }
+
+        if (0..BINGO_ROWS).all(|i| self[i][i].is_none()) {
+            Some(WinningLine::Diagonal)
+        } else if (0..BINGO_ROWS).all(|i| self[i][BINGO_COLS - 1 - i].is_none()) {
+            Some(WinningLine::AntiDiagonal)
+        } else {
+            None
+        }
     }
 
-    fn has_bingo_by_horizontal_line(&self) -> bool {
-        self.rows.iter().any(|r| r.iter().all(|c| c.is_none()))
+    fn winning_horizontal_line(&self) -> Option<WinningLine> {
+        self.rows
+            .iter()
+            .position(|r| r.iter().all(|c| c.is_none()))
+            .map(WinningLine::Row)
     }
 
-    fn has_bingo_by_vertical_line(&self) -> bool {
+    fn winning_vertical_line(&self) -> Option<WinningLine> {
         for x in 0..BINGO_COLS {
             for y in 0..BINGO_ROWS {
                 match self[y][x] {
@@ -75,14 +133,14 @@ impl BingoBoard {
                     }
                     None => {
                         if y == BINGO_ROWS - 1 {
-                            return true;
+                            return Some(WinningLine::Col(x));
                         }
                     }
                 }
             }
         }
 
-        false
+        None
     }
 }
 
@@ -124,70 +182,124 @@ impl<'a> Iterator for BingoNumbersIter<'a> {
     }
 }
 
-fn parse_bingo_line(line: &str) -> BingoLine {
-    let mut res: BingoLine = [None; BINGO_COLS];
-    let mut num_nums = 0;
+#[derive(Debug, PartialEq)]
+enum ParseBingoError {
+    BadCell(String),
+    WrongRowLength { expected: usize, got: usize },
+    WrongBoardRowCount(usize),
+}
 
-    for (idx, c) in line.split_ascii_whitespace().take(BINGO_COLS).enumerate() {
-        let n = c.parse::<u8>().ok();
-        if n.is_none() {
-            panic!("Invalid number as bingo input: {}", c)
+impl fmt::Display for ParseBingoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseBingoError::*;
+        match *self {
+            BadCell(ref c) => write!(f, "Invalid number as bingo input: {}", c),
+            WrongRowLength { expected, got } => write!(
+                f,
+                "Unexpected number of bingo row numbers (should be {}, but was {})",
+                expected, got
+            ),
+            WrongBoardRowCount(n) => write!(
+                f,
+                "Unexpected number of bingo board rows (should be {}, but was {})",
+                BINGO_ROWS, n
+            ),
         }
-        res[idx] = n;
-        num_nums += 1;
     }
+}
+
+fn parse_bingo_line(line: &str) -> Result<BingoLine, ParseBingoError> {
+    let cells: Vec<&str> = line.split_ascii_whitespace().collect();
+
+    if cells.len() != BINGO_COLS {
+        return Err(ParseBingoError::WrongRowLength {
+            expected: BINGO_COLS,
+            got: cells.len(),
+        });
+    }
+
+    let mut res: BingoLine = [None; BINGO_COLS];
 
-    assert!(num_nums == BINGO_COLS);
+    for (idx, c) in cells.into_iter().enumerate() {
+        let n = c
+            .parse::<u8>()
+            .map_err(|_| ParseBingoError::BadCell(c.to_owned()))?;
+        res[idx] = Some(n);
+    }
 
-    res
+    Ok(res)
 }
 
-fn parse_bingo_board(lines: &[&str]) -> BingoBoard {
-    assert!(lines.len() == BINGO_ROWS);
+fn parse_bingo_board(lines: &[&str]) -> Result<BingoBoard, ParseBingoError> {
+    if lines.len() != BINGO_ROWS {
+        return Err(ParseBingoError::WrongBoardRowCount(lines.len()));
+    }
 
     let mut res: BingoLines = [[None; BINGO_COLS]; BINGO_ROWS];
 
     for (idx, r) in lines.iter().enumerate() {
-        res[idx] = parse_bingo_line(r);
+        res[idx] = parse_bingo_line(r)?;
     }
 
-    BingoBoard::new(res)
+    Ok(BingoBoard::new(res))
 }
 
-fn parse_bingo_boards(lines: &[&str]) -> Vec<BingoBoard> {
+fn parse_bingo_boards(lines: &[&str]) -> Result<Vec<BingoBoard>, ParseBingoError> {
     lines.chunks(BINGO_ROWS).map(parse_bingo_board).collect()
 }
 
-type FirstAndLastBingoBoards = (Option<(u8, BingoBoard)>, Option<(u8, BingoBoard)>);
+/// The winning draw, the board, and the line that got the bingo.
+type BingoWin = (u8, BingoBoard, WinningLine);
 
-fn draw_first_and_last_bingo(draws: Vec<u8>, bbs: Vec<BingoBoard>) -> FirstAndLastBingoBoards {
+type FirstAndLastBingoBoards = (Option<BingoWin>, Option<BingoWin>);
+
+/// Returns the boards in the order they got bingo. Boards never getting
+/// bingo are left out.
+fn draw_all_bingo_order(
+    draws: Vec<u8>,
+    bbs: Vec<BingoBoard>,
+    allow_diagonal: bool,
+) -> Vec<BingoWin> {
     let mut obbs: Vec<Option<BingoBoard>> = bbs.into_iter().map(Some).collect();
 
-    let mut fst_bingo: Option<(u8, BingoBoard)> = None;
+    let mut bingos: Vec<BingoWin> = vec![];
 
     for n in draws {
-        for idx in 0..obbs.len() {
-            let obb = &mut obbs[idx];
+        for obb in obbs.iter_mut() {
             if let Some(bb) = obb {
                 bb.mark_draw(n);
-                if bb.has_bingo() {
-                    let found_bingo = take(obb).unwrap();
-                    match fst_bingo {
-                        Some(_) => {
-                            if obbs.iter().flatten().count() == 0 {
-                                return (fst_bingo, Some((n, found_bingo)));
-                            }
-                        }
-                        None => {
-                            fst_bingo = Some((n, found_bingo));
-                        }
-                    }
+                if let Some(line) = bb.winning_line_with(allow_diagonal) {
+                    bingos.push((n, take(obb).unwrap(), line));
                 }
             }
         }
+
+        if obbs.iter().flatten().count() == 0 {
+            break;
+        }
     }
 
-    (fst_bingo, None)
+    bingos
+}
+
+/// The last bingo is returned only if all the boards get bingo, and there
+/// are at least two of them.
+fn draw_first_and_last_bingo(
+    draws: Vec<u8>,
+    bbs: Vec<BingoBoard>,
+    allow_diagonal: bool,
+) -> FirstAndLastBingoBoards {
+    let num_boards = bbs.len();
+
+    let mut bingos = draw_all_bingo_order(draws, bbs, allow_diagonal);
+
+    let lst_bingo = if num_boards > 1 && bingos.len() == num_boards {
+        bingos.pop()
+    } else {
+        None
+    };
+
+    (bingos.into_iter().next(), lst_bingo)
 }
 
 /// CLI usage: cargo run -- input.txt
@@ -205,17 +317,20 @@ fn main() {
     let bingo_boards = {
         let ref_lines: Vec<&str> = lines[1..].iter().map(AsRef::as_ref).collect();
 
-        parse_bingo_boards(&ref_lines[..])
+        parse_bingo_boards(&ref_lines[..]).unwrap_or_else(|err| {
+            eprintln!("Invalid bingo boards: {}", err);
+            process::exit(1);
+        })
     };
 
-    let (fst_bingo, lst_bingo) = draw_first_and_last_bingo(draws, bingo_boards);
+    let (fst_bingo, lst_bingo) = draw_first_and_last_bingo(draws, bingo_boards, false);
 
-    if let Some((n, bb)) = fst_bingo {
-        println!("first bingo score: {}", (n as u32) * bb.numbers_sum());
+    if let Some((n, bb, line)) = fst_bingo {
+        println!("first bingo score: {} ({})", bb.score(n), line);
     }
 
-    if let Some((n, bb)) = lst_bingo {
-        println!("last bingo score:  {}", (n as u32) * bb.numbers_sum());
+    if let Some((n, bb, line)) = lst_bingo {
+        println!("last bingo score:  {} ({})", bb.score(n), line);
     }
 }
 
@@ -233,8 +348,9 @@ mod tests {
                 "71 23 64 93 14",
                 "80 46 76 65 33",
             ][..],
-        );
-        assert!(!bb.has_bingo());
+        )
+        .unwrap();
+        assert_eq!(bb.winning_line(), None);
     }
 
     #[test]
@@ -247,11 +363,12 @@ mod tests {
                 "71 23 64 93 14",
                 "80 46 76 65 33",
             ][..],
-        );
+        )
+        .unwrap();
         for draw in [93, 14, 71, 23, 64] {
             bb.mark_draw(draw);
         }
-        assert!(bb.has_bingo());
+        assert_eq!(bb.winning_line(), Some(WinningLine::Row(3)));
     }
 
     #[test]
@@ -264,10 +381,143 @@ mod tests {
                 "71 23 64 93 14",
                 "80 46 76 65 33",
             ][..],
-        );
+        )
+        .unwrap();
         for draw in [82, 93, 50, 22, 65] {
             bb.mark_draw(draw);
         }
-        assert!(bb.has_bingo());
+        assert_eq!(bb.winning_line(), Some(WinningLine::Col(3)));
+    }
+
+    #[test]
+    fn bingo_by_diagonal_line() {
+        let mut bb = parse_bingo_board(
+            &vec![
+                "29 58 10 50 19",
+                "47  4 51 22 69",
+                "66  5 83 82 25",
+                "71 23 64 93 14",
+                "80 46 76 65 33",
+            ][..],
+        )
+        .unwrap();
+        for draw in [19, 22, 83, 23, 80] {
+            bb.mark_draw(draw);
+        }
+        assert_eq!(bb.winning_diagonal_line(), Some(WinningLine::AntiDiagonal));
+        assert_eq!(bb.winning_line_with(true), Some(WinningLine::AntiDiagonal));
+        assert_eq!(bb.winning_line_with(false), None);
+        assert_eq!(bb.winning_line(), None);
+    }
+
+    const EXAMPLE_DRAWS: [u8; 27] = [
+        7, 4, 9, 5, 11, 17, 23, 2, 0, 14, 21, 24, 10, 16, 13, 6, 15, 25, 12, 22, 18, 20, 8, 19, 3,
+        26, 1,
+    ];
+
+    const EXAMPLE_BOARDS: [&str; 15] = [
+        "22 13 17 11  0",
+        " 8  2 23  4 24",
+        "21  9 14 16  7",
+        " 6 10  3 18  5",
+        " 1 12 20 15 19",
+        " 3 15  0  2 22",
+        " 9 18 13 17  5",
+        "19  8  7 25 23",
+        "20 11 10 24  4",
+        "14 21 16 12  6",
+        "14 21 17 24  4",
+        "10 16 15  9 19",
+        "18  8 23 26 20",
+        "22 11 13  6  5",
+        " 2  0 12  3  7",
+    ];
+
+    #[test]
+    fn draw_first_and_last_bingo_with_example() {
+        let (fst_bingo, lst_bingo) = draw_first_and_last_bingo(
+            EXAMPLE_DRAWS.to_vec(),
+            parse_bingo_boards(&EXAMPLE_BOARDS).unwrap(),
+            false,
+        );
+
+        let (fst_n, fst_bb, fst_line) = fst_bingo.unwrap();
+        assert_eq!((fst_n, fst_line), (24, WinningLine::Row(0)));
+        assert_eq!(fst_bb.score(fst_n), 4512);
+
+        let (lst_n, lst_bb, lst_line) = lst_bingo.unwrap();
+        assert_eq!((lst_n, lst_line), (13, WinningLine::Col(2)));
+        assert_eq!(lst_bb.score(lst_n), 1924);
+    }
+
+    #[test]
+    fn score_of_first_winner_in_example() {
+        let mut bb = parse_bingo_board(&EXAMPLE_BOARDS[10..15]).unwrap();
+        for draw in &EXAMPLE_DRAWS[..12] {
+            bb.mark_draw(*draw);
+        }
+        assert_eq!(bb.winning_line(), Some(WinningLine::Row(0)));
+        assert_eq!(bb.score(24), 4512);
+    }
+
+    #[test]
+    fn draw_all_bingo_order_with_example() {
+        let bingos = draw_all_bingo_order(
+            EXAMPLE_DRAWS.to_vec(),
+            parse_bingo_boards(&EXAMPLE_BOARDS).unwrap(),
+            false,
+        );
+
+        assert_eq!(
+            bingos
+                .iter()
+                .map(|(n, bb, line)| (*n, bb[0][0], *line))
+                .collect::<Vec<_>>(),
+            vec![
+                (24, None, WinningLine::Row(0)),
+                (16, Some(22), WinningLine::Row(2)),
+                (13, Some(3), WinningLine::Col(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_bingo_board_when_invalid() {
+        assert_eq!(
+            parse_bingo_board(
+                &[
+                    "29 58 10 50 19",
+                    "47  4 51 x2 69",
+                    "66  5 83 82 25",
+                    "71 23 64 93 14",
+                    "80 46 76 65 33",
+                ][..]
+            )
+            .unwrap_err(),
+            ParseBingoError::BadCell("x2".to_owned())
+        );
+        assert_eq!(
+            parse_bingo_board(
+                &[
+                    "29 58 10 50 19",
+                    "47  4 51 22 69",
+                    "66  5 83 82 25",
+                    "71 23 64 93 14",
+                ][..]
+            )
+            .unwrap_err(),
+            ParseBingoError::WrongBoardRowCount(4)
+        );
+        assert_eq!(
+            parse_bingo_line("29 58 10 50").unwrap_err(),
+            ParseBingoError::WrongRowLength {
+                expected: 5,
+                got: 4
+            }
+        );
+        assert_eq!(
+            parse_bingo_boards(&EXAMPLE_BOARDS[..14]).unwrap_err(),
+            ParseBingoError::WrongBoardRowCount(4)
+        );
     }
 }
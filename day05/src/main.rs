@@ -4,8 +4,11 @@ use nom::sequence::{delimited, separated_pair};
 use nom::{Finish, IResult};
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::process;
+use std::str::FromStr;
 
 type Point = (u32, u32);
 
@@ -34,13 +37,28 @@ impl Line {
         dx.abs() == dy.abs()
     }
 
-    fn points(self: &Line) -> Vec<Point> {
-        if !(self.is_horizontal() || self.is_vertical() || self.is_diagonal_45deg()) {
-            panic!("Unsupported line angle: {:?}", self);
-        }
+    /// Whether the line passes through lattice points in between its
+    /// begin and end points.
+    fn is_collinear_integer(self: &Line) -> bool {
+        let (dx, dy) = self.delta();
+        gcd(dx.unsigned_abs(), dy.unsigned_abs()) > 1
+    }
+
+    fn delta(self: &Line) -> (i32, i32) {
+        (
+            self.end.0 as i32 - self.begin.0 as i32,
+            self.end.1 as i32 - self.begin.1 as i32,
+        )
+    }
 
-        let ddx = (self.end.0 as i32 - self.begin.0 as i32).signum();
-        let ddy = (self.end.1 as i32 - self.begin.1 as i32).signum();
+    /// Returns the grid cells the line crosses, walking from begin to end
+    /// with Bresenham's algorithm. Horizontal, vertical, and 45° diagonal
+    /// lines cross exactly the lattice points on them.
+    fn points(self: &Line) -> Vec<Point> {
+        let (dx, dy) = self.delta();
+        let (sx, sy) = (dx.signum(), dy.signum());
+        let (dx, dy) = (dx.abs(), -dy.abs());
+        let mut err = dx + dy;
 
         let mut p = self.begin;
         let mut points = Vec::new();
@@ -52,14 +70,31 @@ impl Line {
                 break;
             }
 
-            p.0 = (p.0 as i32 + ddx) as u32;
-            p.1 = (p.1 as i32 + ddy) as u32;
+            let e2 = 2 * err;
+
+            if e2 >= dy {
+                err += dy;
+                p.0 = (p.0 as i32 + sx) as u32;
+            }
+
+            if e2 <= dx {
+                err += dx;
+                p.1 = (p.1 as i32 + sy) as u32;
+            }
         }
 
         points
     }
 }
 
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 fn parse_point(input: &str) -> IResult<&str, Point> {
     separated_pair(u32, tag(","), u32)(input)
 }
@@ -74,6 +109,97 @@ fn parse_line(input: &str) -> IResult<&str, Line> {
     Ok((unconsumed, line))
 }
 
+#[derive(Debug, PartialEq)]
+enum ParseLineError {
+    /// The unparsed input where parsing failed
+    Syntax(String),
+    TrailingInput(String),
+}
+
+impl fmt::Display for ParseLineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseLineError::*;
+        match *self {
+            Syntax(ref input) => write!(f, "Invalid line syntax at \"{}\"", input),
+            TrailingInput(ref input) => write!(f, "Unexpected trailing input \"{}\"", input),
+        }
+    }
+}
+
+impl FromStr for Line {
+    type Err = ParseLineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_line(s).finish() {
+            Ok(("", line)) => Ok(line),
+            Ok((unconsumed, _)) => Err(ParseLineError::TrailingInput(unconsumed.to_owned())),
+            Err(err) => Err(ParseLineError::Syntax(err.input.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ReadLinesError {
+    Io {
+        line_num: usize,
+        err: io::Error,
+    },
+    Parse {
+        line_num: usize,
+        line: String,
+        err: ParseLineError,
+    },
+}
+
+impl fmt::Display for ReadLinesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ReadLinesError::*;
+        match *self {
+            Io { line_num, ref err } => write!(f, "Cannot read line {}: {}", line_num, err),
+            Parse {
+                line_num,
+                ref line,
+                ref err,
+            } => write!(f, "Unknown line {} ({}): {}", line_num, err, line),
+        }
+    }
+}
+
+/// Parses each line as a line segment. Line numbers in errors are 1-based.
+fn read_lines(reader: impl BufRead) -> Result<Vec<Line>, ReadLinesError> {
+    reader
+        .lines()
+        .enumerate()
+        .map(|(idx, l)| {
+            let line_num = idx + 1;
+            let line = l.map_err(|err| ReadLinesError::Io { line_num, err })?;
+            line.parse().map_err(|err| ReadLinesError::Parse {
+                line_num,
+                line,
+                err,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+struct OutOfBounds {
+    point: Point,
+    max: Point,
+}
+
+#[cfg(test)]
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Point {},{} is out of bounds (max is {},{})",
+            self.point.0, self.point.1, self.max.0, self.max.1
+        )
+    }
+}
+
 #[derive(Debug)]
 struct Space {
     points: HashMap<Point, u32>,
@@ -93,27 +219,102 @@ impl Space {
         }
     }
 
-    fn count_points_with_overlaps(self: &Space, min_overlap: u32) -> usize {
+    /// Draws the line only if all its points are within `(0, 0)` to `max`,
+    /// inclusive. Checking the endpoints is enough, as the rest of the
+    /// points are between them.
+    #[cfg(test)]
+    fn draw_line_bounded(self: &mut Space, line: &Line, max: Point) -> Result<(), OutOfBounds> {
+        for &point in [line.begin, line.end].iter() {
+            if point.0 > max.0 || point.1 > max.1 {
+                return Err(OutOfBounds { point, max });
+            }
+        }
+
+        self.draw_line(line);
+
+        Ok(())
+    }
+
+    fn points_with_overlaps(
+        self: &Space,
+        min_overlap: u32,
+    ) -> impl Iterator<Item = (Point, u32)> + '_ {
         self.points
             .iter()
-            .filter(|(_, v)| **v >= min_overlap)
+            .filter(move |(_, v)| **v >= min_overlap)
+            .map(|(p, v)| (*p, *v))
+    }
+
+    fn count_points_with_overlaps(self: &Space, min_overlap: u32) -> usize {
+        self.points_with_overlaps(min_overlap).count()
+    }
+
+    /// Counts only the points within `rect`, given as its min and max
+    /// corners, inclusive.
+    #[cfg(test)]
+    fn count_points_with_overlaps_in(
+        self: &Space,
+        min_overlap: u32,
+        rect: (Point, Point),
+    ) -> usize {
+        let ((min_x, min_y), (max_x, max_y)) = rect;
+        self.points_with_overlaps(min_overlap)
+            .filter(|&((x, y), _)| min_x <= x && x <= max_x && min_y <= y && y <= max_y)
             .count()
     }
+
+    /// Returns the min and max corners of the area containing all drawn
+    /// points, or `None` if nothing has been drawn.
+    fn bounds(self: &Space) -> Option<(Point, Point)> {
+        self.points.keys().fold(None, |acc, &(x, y)| match acc {
+            None => Some(((x, y), (x, y))),
+            Some(((min_x, min_y), (max_x, max_y))) => {
+                Some(((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y))))
+            }
+        })
+    }
+
+    /// Renders the overlap counts of the area from `(0, 0)` to
+    /// `(width - 1, height - 1)`, one row per line. Points without lines
+    /// are shown as `.`, and points with more than 9 overlaps as `#`.
+    fn render(self: &Space, width: u32, height: u32) -> String {
+        let mut res = String::with_capacity(((width + 1) * height) as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let c = match self.points.get(&(x, y)) {
+                    None | Some(0) => '.',
+                    Some(&n) => char::from_digit(n, 10).unwrap_or('#'),
+                };
+                res.push(c);
+            }
+            res.push('\n');
+        }
+
+        res
+    }
+}
+
+impl fmt::Display for Space {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (width, height) = self
+            .bounds()
+            .map_or((0, 0), |(_, (max_x, max_y))| (max_x + 1, max_y + 1));
+        write!(f, "{}", self.render(width, height))
+    }
 }
 
 /// CLI usage: cargo run -- input.txt
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
-    let lines: Vec<Line> = io::BufReader::new(File::open(filename).expect("File not found"))
-        .lines()
-        .map(|l| {
-            parse_line(&l.expect("Line not UTF-8"))
-                .finish()
-                .expect("Unknown line")
-                .1
-        })
-        .collect();
+    let lines = read_lines(io::BufReader::new(
+        File::open(filename).expect("File not found"),
+    ))
+    .unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
 
     let num_points_from_hv_lines_with_min_2_overlaps = {
         let mut space = Space::new();
@@ -146,6 +347,24 @@ fn main() {
         "Num points from horizontal/vertical/diagonal lines with min. 2 overlaps: {}",
         num_points_from_hvd_lines_with_min_2_overlaps
     );
+
+    let num_points_from_all_lines_with_min_2_overlaps = {
+        let mut space = Space::new();
+        for l in &lines {
+            space.draw_line(l);
+        }
+        space.count_points_with_overlaps(2)
+    };
+
+    println!(
+        "Num points from lines of any slope with min. 2 overlaps: {}",
+        num_points_from_all_lines_with_min_2_overlaps
+    );
+
+    println!(
+        "  num lines through inner lattice points: {}",
+        lines.iter().filter(|l| l.is_collinear_integer()).count()
+    );
 }
 
 #[cfg(test)]
@@ -190,4 +409,173 @@ mod tests {
             vec![(9, 4), (8, 5), (7, 6), (6, 7), (5, 8), (4, 9)]
         );
     }
+
+    #[test]
+    fn points_on_slope_2_to_1_line() {
+        let l = Line::new((0, 0), (4, 2));
+        assert!(!l.is_horizontal());
+        assert!(!l.is_vertical());
+        assert!(!l.is_diagonal_45deg());
+        assert_eq!(l.points(), vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+    }
+
+    #[test]
+    fn is_collinear_integer_when_inner_lattice_points() {
+        assert!(Line::new((0, 0), (4, 2)).is_collinear_integer());
+        assert!(Line::new((0, 0), (3, 3)).is_collinear_integer());
+        assert!(!Line::new((3, 0), (0, 2)).is_collinear_integer());
+        assert!(!Line::new((0, 0), (1, 1)).is_collinear_integer());
+    }
+
+    #[test]
+    fn points_on_line_at_shallow_slope() {
+        let l = Line::new((3, 0), (0, 2));
+        assert_eq!(l.points(), vec![(3, 0), (2, 1), (1, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn parse_line_from_str() {
+        assert_eq!("0,9 -> 5,9".parse(), Ok(Line::new((0, 9), (5, 9))));
+        assert_eq!(
+            "0,9 -> 5".parse::<Line>(),
+            Err(ParseLineError::Syntax("".to_owned()))
+        );
+        assert_eq!(
+            "0,9 -> 5,9 -> 1,1".parse::<Line>(),
+            Err(ParseLineError::TrailingInput(" -> 1,1".to_owned()))
+        );
+    }
+
+    #[test]
+    fn read_lines_when_malformed() {
+        let input = "0,9 -> 5,9\n8,0 -> 0,8\n9,4 -> 3\n2,2 -> 2,1\n";
+        let err = read_lines(input.as_bytes()).unwrap_err();
+        assert!(matches!(
+            err,
+            ReadLinesError::Parse {
+                line_num: 3,
+                ref line,
+                ..
+            } if line == "9,4 -> 3"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unknown line 3 (Invalid line syntax at \"\"): 9,4 -> 3"
+        );
+        assert_eq!(read_lines("0,9 -> 5,9\n".as_bytes()).unwrap().len(), 1);
+    }
+
+    const EXAMPLE_LINES: [&str; 10] = [
+        "0,9 -> 5,9",
+        "8,0 -> 0,8",
+        "9,4 -> 3,4",
+        "2,2 -> 2,1",
+        "7,0 -> 7,4",
+        "6,4 -> 2,0",
+        "0,9 -> 2,9",
+        "3,4 -> 1,4",
+        "0,0 -> 8,8",
+        "5,5 -> 8,2",
+    ];
+
+    fn example_lines() -> Vec<Line> {
+        EXAMPLE_LINES.iter().map(|l| l.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn bounds_of_empty_space() {
+        assert_eq!(Space::new().bounds(), None);
+    }
+
+    #[test]
+    fn bounds_of_space_with_lines() {
+        let mut space = Space::new();
+        space.draw_line(&Line::new((3, 4), (3, 7)));
+        space.draw_line(&Line::new((5, 2), (8, 5)));
+        space.draw_line(&Line::new((6, 6), (4, 6)));
+        assert_eq!(space.bounds(), Some(((3, 2), (8, 7))));
+    }
+
+    #[test]
+    fn draw_line_bounded_when_out_of_bounds() {
+        let mut space = Space::new();
+        assert_eq!(
+            space.draw_line_bounded(&Line::new((0, 9), (5, 9)), (9, 9)),
+            Ok(())
+        );
+        assert_eq!(
+            space.draw_line_bounded(&Line::new((8, 0), (12, 4)), (9, 9)),
+            Err(OutOfBounds {
+                point: (12, 4),
+                max: (9, 9)
+            })
+        );
+        assert_eq!(space.bounds(), Some(((0, 9), (5, 9))));
+        assert_eq!(space.points.len(), 6);
+    }
+
+    #[test]
+    fn points_with_overlaps_with_hv_lines() {
+        let mut space = Space::new();
+        for l in example_lines()
+            .iter()
+            .filter(|l| l.is_horizontal() || l.is_vertical())
+        {
+            space.draw_line(l);
+        }
+        let mut points: Vec<(Point, u32)> = space.points_with_overlaps(2).collect();
+        points.sort();
+        assert_eq!(
+            points,
+            vec![
+                ((0, 9), 2),
+                ((1, 9), 2),
+                ((2, 9), 2),
+                ((3, 4), 2),
+                ((7, 4), 2)
+            ]
+        );
+        assert_eq!(space.count_points_with_overlaps(2), 5);
+    }
+
+    #[test]
+    fn count_points_with_overlaps_in_rect() {
+        let mut space = Space::new();
+        for l in example_lines().iter() {
+            space.draw_line(l);
+        }
+        assert_eq!(space.count_points_with_overlaps(2), 12);
+        assert_eq!(space.count_points_with_overlaps_in(2, ((0, 0), (4, 4))), 3);
+        assert_eq!(space.count_points_with_overlaps_in(3, ((0, 0), (4, 4))), 1);
+        assert_eq!(space.count_points_with_overlaps_in(2, ((0, 9), (9, 9))), 3);
+        assert_eq!(space.count_points_with_overlaps_in(2, ((9, 0), (9, 9))), 0);
+    }
+
+    #[test]
+    fn display_space_with_hv_lines() {
+        let mut space = Space::new();
+        for l in example_lines()
+            .iter()
+            .filter(|l| l.is_horizontal() || l.is_vertical())
+        {
+            space.draw_line(l);
+        }
+        assert_eq!(
+            space.to_string(),
+            [
+                ".......1..",
+                "..1....1..",
+                "..1....1..",
+                ".......1..",
+                ".112111211",
+                "..........",
+                "..........",
+                "..........",
+                "..........",
+                "222111....",
+                "",
+            ]
+            .join("\n")
+        );
+    }
 }
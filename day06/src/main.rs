@@ -1,82 +1,279 @@
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::num::ParseIntError;
-
-fn parse_fish_state_line(s: &str) -> Result<Vec<u8>, ParseIntError> {
-    s.split(',').map(|n| n.parse::<u8>()).collect()
-}
+use std::str::FromStr;
 
 const NEW_FISH_TIMER: u8 = 8;
 const FISH_RESET_TIMER: u8 = 6;
 
+#[derive(Debug, PartialEq)]
+enum ParseFishSwarmError {
+    InvalidTimer { timer: String, err: ParseIntError },
+    TimerOutOfRange(u8),
+}
+
+impl fmt::Display for ParseFishSwarmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseFishSwarmError::*;
+        match *self {
+            InvalidTimer { ref timer, ref err } => {
+                write!(f, "Invalid timer \"{}\": {}", timer, err)
+            }
+            TimerOutOfRange(timer) => write!(
+                f,
+                "Timer out of range: {} (max is {})",
+                timer, NEW_FISH_TIMER
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct OverflowError {
+    day: u32,
+}
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Number of fishes overflows at day {}", self.day)
+    }
+}
+
 #[derive(Debug, Clone)]
 struct FishSwarm {
-    pub num_fishes_by_timer: [u64; NEW_FISH_TIMER as usize + 1],
+    /// Indexed by timer, from 0 to the timer of a new fish
+    pub num_fishes_by_timer: Vec<u128>,
+    reset_timer: u8,
 }
 
 impl FishSwarm {
-    fn new(fish_timers: &Vec<u8>) -> FishSwarm {
-        let mut arr = [0; NEW_FISH_TIMER as usize + 1];
+    fn new(fish_timers: &[u8]) -> FishSwarm {
+        FishSwarm::with_timers(fish_timers, FISH_RESET_TIMER, NEW_FISH_TIMER)
+    }
+
+    /// `reset` is the timer of a fish after spawning, and `spawn` is the
+    /// timer of a new fish.
+    fn with_timers(fish_timers: &[u8], reset: u8, spawn: u8) -> FishSwarm {
+        assert!(reset <= spawn, "Reset timer must not exceed spawn timer");
+
+        let mut arr = vec![0; spawn as usize + 1];
 
         for &fish_timer in fish_timers {
+            assert!(
+                fish_timer <= spawn,
+                "Fish timer {} exceeds spawn timer {}",
+                fish_timer,
+                spawn
+            );
             arr[fish_timer as usize] += 1;
         }
 
         FishSwarm {
             num_fishes_by_timer: arr,
+            reset_timer: reset,
         }
     }
 
-    fn simulate_fish_spawns_in_day(self: &mut FishSwarm) {
-        let mut arr = [0; NEW_FISH_TIMER as usize + 1];
+    /// Returns `false` if the number of fishes would overflow, leaving the
+    /// swarm unchanged.
+    fn simulate_fish_spawns_in_day(self: &mut FishSwarm) -> bool {
+        let arr = match spawn_fishes_in_day(
+            &self.num_fishes_by_timer,
+            self.reset_timer,
+            u128::checked_add,
+        ) {
+            Some(arr) => arr,
+            None => return false,
+        };
 
-        for (timer, &num_fishes) in self.num_fishes_by_timer.iter().enumerate() {
-            if timer == 0 {
-                arr[NEW_FISH_TIMER as usize] = num_fishes;
-                arr[FISH_RESET_TIMER as usize] = num_fishes;
-            } else {
-                arr[timer - 1] += num_fishes;
-            }
+        if checked_sum(&arr).is_none() {
+            return false;
         }
 
         self.num_fishes_by_timer = arr;
+
+        true
     }
 
     fn simulate_fish_spawns(self: &mut FishSwarm, num_days: u32) {
+        self.simulate_fish_spawns_checked(num_days)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    /// On overflow, the swarm is left in its state at the end of the day
+    /// before.
+    fn simulate_fish_spawns_checked(
+        self: &mut FishSwarm,
+        num_days: u32,
+    ) -> Result<(), OverflowError> {
+        for day in 1..=num_days {
+            if !self.simulate_fish_spawns_in_day() {
+                return Err(OverflowError { day });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of fishes at the end of each day.
+    #[cfg(test)]
+    fn simulate_with_history(self: &mut FishSwarm, num_days: u32) -> Vec<u128> {
+        (1..=num_days)
+            .map(|day| {
+                if !self.simulate_fish_spawns_in_day() {
+                    panic!("{}", OverflowError { day });
+                }
+                self.sum_fishes()
+            })
+            .collect()
+    }
+
+    /// Returns the number of fishes at the end of each of `days`, in the
+    /// order given, simulating only once up to the last of them. Leaves the
+    /// swarm unchanged.
+    fn simulate_snapshots(self: &FishSwarm, days: &[u32]) -> Vec<(u32, u128)> {
+        let mut sorted_days = days.to_vec();
+        sorted_days.sort_unstable();
+        sorted_days.dedup();
+
+        let mut swarm = self.clone();
+        let mut current_day = 0;
+        let mut totals: HashMap<u32, u128> = HashMap::new();
+
+        for day in sorted_days {
+            swarm.simulate_fish_spawns(day - current_day);
+            current_day = day;
+            totals.insert(day, swarm.sum_fishes());
+        }
+
+        days.iter().map(|day| (*day, totals[day])).collect()
+    }
+
+    /// Returns the first day at the end of which the number of fishes
+    /// exceeds `threshold`, or `None` if that doesn't happen within
+    /// `max_days`. Returns 0 if the swarm already exceeds the threshold.
+    /// Leaves the swarm unchanged.
+    fn days_until_exceeds(self: &FishSwarm, threshold: u64, max_days: u32) -> Option<u32> {
+        let threshold = threshold as u128;
+
+        if self.sum_fishes() > threshold {
+            return Some(0);
+        }
+
+        let mut swarm = self.clone();
+
+        // overflow means the number exceeds even `u128::MAX`
+        (1..=max_days)
+            .find(|_| !swarm.simulate_fish_spawns_in_day() || swarm.sum_fishes() > threshold)
+    }
+
+    /// The base-10 logarithm of the number of fishes, for plotting. Returns
+    /// negative infinity for an empty swarm.
+    #[cfg(test)]
+    fn population_log10(self: &FishSwarm) -> f64 {
+        self.population_log10_after(0)
+    }
+
+    /// Like `population_log10`, but after simulating `num_days` more days
+    /// with floating point counts, which don't overflow like the exact
+    /// counts do. Leaves the swarm unchanged.
+    fn population_log10_after(self: &FishSwarm, num_days: u32) -> f64 {
+        let mut arr: Vec<f64> = self.num_fishes_by_timer.iter().map(|&n| n as f64).collect();
+
         for _ in 0..num_days {
-            self.simulate_fish_spawns_in_day();
+            arr = spawn_fishes_in_day(&arr, self.reset_timer, |a, b| Some(a + b)).unwrap();
         }
+
+        arr.iter().sum::<f64>().log10()
     }
 
-    fn sum_fishes(self: &FishSwarm) -> u64 {
+    fn sum_fishes(self: &FishSwarm) -> u128 {
         self.num_fishes_by_timer.iter().sum()
     }
 }
 
+/// Parses a comma separated line of fish timers.
+impl FromStr for FishSwarm {
+    type Err = ParseFishSwarmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let timers = s
+            .split(',')
+            .map(|t| {
+                let timer = t
+                    .parse::<u8>()
+                    .map_err(|err| ParseFishSwarmError::InvalidTimer {
+                        timer: t.to_owned(),
+                        err,
+                    })?;
+
+                if timer > NEW_FISH_TIMER {
+                    Err(ParseFishSwarmError::TimerOutOfRange(timer))
+                } else {
+                    Ok(timer)
+                }
+            })
+            .collect::<Result<Vec<u8>, ParseFishSwarmError>>()?;
+
+        Ok(FishSwarm::new(&timers))
+    }
+}
+
+/// Returns the number of fishes by timer at the end of the day, or `None`
+/// if `add` fails.
+fn spawn_fishes_in_day<T, F>(num_fishes_by_timer: &[T], reset_timer: u8, add: F) -> Option<Vec<T>>
+where
+    T: Copy + Default,
+    F: Fn(T, T) -> Option<T>,
+{
+    let spawn_timer = num_fishes_by_timer.len() - 1;
+    let mut arr = vec![T::default(); spawn_timer + 1];
+
+    for (timer, &num_fishes) in num_fishes_by_timer.iter().enumerate() {
+        if timer == 0 {
+            arr[spawn_timer] = num_fishes;
+            // the reset and spawn timers may be the same
+            arr[reset_timer as usize] = add(arr[reset_timer as usize], num_fishes)?;
+        } else {
+            arr[timer - 1] = add(arr[timer - 1], num_fishes)?;
+        }
+    }
+
+    Some(arr)
+}
+
+fn checked_sum(nums: &[u128]) -> Option<u128> {
+    nums.iter().try_fold(0u128, |acc, &n| acc.checked_add(n))
+}
+
 /// CLI usage: cargo run -- input.txt
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
-    let swarm: FishSwarm = {
-        let timers: Vec<u8> = io::BufReader::new(File::open(filename).expect("File not found"))
-            .lines()
-            .next()
-            .expect("File is empty")
-            .map(|l| {
-                parse_fish_state_line(&l)
-                    .unwrap_or_else(|err| panic!("Invalid fish state line: {}", err))
-            })
-            .expect("Line not UTF-8");
+    let swarm: FishSwarm = io::BufReader::new(File::open(filename).expect("File not found"))
+        .lines()
+        .next()
+        .expect("File is empty")
+        .expect("Line not UTF-8")
+        .parse()
+        .unwrap_or_else(|err| panic!("Invalid fish state line: {}", err));
 
-        FishSwarm::new(&timers)
-    };
+    for (days, num_fishes) in swarm.simulate_snapshots(&[80, 256]) {
+        println!("Number of fishes after {} days: {}", days, num_fishes);
+    }
 
-    for days in [80, 256] {
-        let mut s = swarm.clone();
-        s.simulate_fish_spawns(days);
-        println!("Number of fishes after {} days: {}", days, s.sum_fishes());
+    match swarm.days_until_exceeds(u64::MAX, 1000) {
+        Some(day) => println!("Number of fishes exceeds u64 max after {} days", day),
+        None => println!("Number of fishes doesn't exceed u64 max in 1000 days"),
     }
+
+    println!(
+        "Log10 of number of fishes after 1000 days: {:.3}",
+        swarm.population_log10_after(1000)
+    );
 }
 
 #[cfg(test)]
@@ -85,10 +282,132 @@ mod tests {
 
     #[test]
     fn test_it() {
-        let mut swarm = FishSwarm::new(&vec![3, 4, 3, 1, 2]);
+        let mut swarm = FishSwarm::new(&[3, 4, 3, 1, 2]);
         swarm.simulate_fish_spawns_in_day();
         assert_eq!(swarm.num_fishes_by_timer, [1, 1, 2, 1, 0, 0, 0, 0, 0]);
         swarm.simulate_fish_spawns_in_day();
         assert_eq!(swarm.num_fishes_by_timer, [1, 2, 1, 0, 0, 0, 1, 0, 1]);
     }
+
+    #[test]
+    fn simulate_1000_days_without_overflow() {
+        let mut swarm = FishSwarm::new(&[8]);
+        assert_eq!(swarm.simulate_fish_spawns_checked(1000), Ok(()));
+        assert!(swarm.sum_fishes() > u64::MAX as u128);
+    }
+
+    #[test]
+    fn simulate_until_overflow() {
+        let mut swarm = FishSwarm::new(&[3, 4, 3, 1, 2]);
+        assert_eq!(
+            swarm.simulate_fish_spawns_checked(1000),
+            Err(OverflowError { day: 999 })
+        );
+    }
+
+    #[test]
+    fn simulate_with_custom_timers() {
+        let mut swarm = FishSwarm::with_timers(&[3, 4, 3, 1, 2], 4, 6);
+        swarm.simulate_fish_spawns_in_day();
+        assert_eq!(swarm.num_fishes_by_timer, [1, 1, 2, 1, 0, 0, 0]);
+        swarm.simulate_fish_spawns_in_day();
+        assert_eq!(swarm.num_fishes_by_timer, [1, 2, 1, 0, 1, 0, 1]);
+        swarm.simulate_fish_spawns_in_day();
+        assert_eq!(swarm.num_fishes_by_timer, [2, 1, 0, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn simulate_with_same_reset_and_spawn_timers() {
+        let mut swarm = FishSwarm::with_timers(&[0, 1], 2, 2);
+        swarm.simulate_fish_spawns_in_day();
+        assert_eq!(swarm.num_fishes_by_timer, [1, 0, 2]);
+        swarm.simulate_fish_spawns_in_day();
+        assert_eq!(swarm.num_fishes_by_timer, [0, 2, 2]);
+        swarm.simulate_fish_spawns_in_day();
+        assert_eq!(swarm.num_fishes_by_timer, [2, 2, 0]);
+        swarm.simulate_fish_spawns_in_day();
+        assert_eq!(swarm.num_fishes_by_timer, [2, 0, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Fish timer 7 exceeds spawn timer 6")]
+    fn with_timers_when_fish_timer_out_of_range() {
+        FishSwarm::with_timers(&[3, 7], 4, 6);
+    }
+
+    #[test]
+    fn simulate_with_history() {
+        let mut swarm = FishSwarm::new(&[3, 4, 3, 1, 2]);
+        let history = swarm.simulate_with_history(80);
+        assert_eq!(history.len(), 80);
+        assert_eq!(history[..3], [5, 6, 7]);
+        assert_eq!(history[17], 26);
+        assert_eq!(history[79], 5934);
+    }
+
+    #[test]
+    fn simulate_snapshots() {
+        let swarm = FishSwarm::new(&[3, 4, 3, 1, 2]);
+        assert_eq!(
+            swarm.simulate_snapshots(&[18, 80]),
+            vec![(18, 26), (80, 5934)]
+        );
+        assert_eq!(
+            swarm.simulate_snapshots(&[80, 0, 18]),
+            vec![(80, 5934), (0, 5), (18, 26)]
+        );
+        assert_eq!(swarm.sum_fishes(), 5);
+        assert!(swarm.simulate_snapshots(&[500])[0].1 > u64::MAX as u128);
+    }
+
+    #[test]
+    fn days_until_exceeds() {
+        let swarm = FishSwarm::new(&[3, 4, 3, 1, 2]);
+        assert_eq!(swarm.days_until_exceeds(26, 80), Some(19));
+        assert_eq!(swarm.days_until_exceeds(26, 18), None);
+        assert_eq!(swarm.days_until_exceeds(4, 80), Some(0));
+        assert_eq!(swarm.days_until_exceeds(u64::MAX, 1000), Some(490));
+        assert_eq!(swarm.sum_fishes(), 5);
+    }
+
+    #[test]
+    fn population_log10() {
+        let swarm = FishSwarm::new(&[3, 4, 3, 1, 2]);
+        assert_eq!(swarm.population_log10(), 5f64.log10());
+
+        let log10_at_1000 = swarm.population_log10_after(1000);
+        assert!(log10_at_1000.is_finite());
+
+        let log10_at_2000 = swarm.population_log10_after(2000);
+        assert!(log10_at_2000.is_finite());
+        assert!(log10_at_2000 > log10_at_1000);
+
+        let mut exact = swarm.clone();
+        exact.simulate_fish_spawns(256);
+        assert_eq!(
+            swarm.population_log10_after(256).floor(),
+            exact.sum_fishes().ilog10() as f64
+        );
+
+        assert_eq!(FishSwarm::new(&[]).population_log10(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn parse_fish_swarm() {
+        let swarm: FishSwarm = "3,4,3,1,2".parse().unwrap();
+        assert_eq!(swarm.num_fishes_by_timer, [0, 1, 1, 2, 1, 0, 0, 0, 0]);
+        assert_eq!(swarm.sum_fishes(), 5);
+    }
+
+    #[test]
+    fn parse_fish_swarm_when_invalid() {
+        assert_eq!(
+            "3,9,1".parse::<FishSwarm>().unwrap_err(),
+            ParseFishSwarmError::TimerOutOfRange(9)
+        );
+        assert_eq!(
+            "3,x".parse::<FishSwarm>().unwrap_err().to_string(),
+            "Invalid timer \"x\": invalid digit found in string"
+        );
+    }
 }
@@ -1,11 +1,31 @@
 use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::num::ParseIntError;
+use std::ops::RangeInclusive;
+use std::process;
 
-fn parse_positions_line(s: &str) -> Result<BTreeMap<u16, u32>, ParseIntError> {
-    let poses = s.split(',').map(|n| n.parse::<u16>());
+#[derive(Debug)]
+struct ParsePositionError {
+    position: String,
+    err: ParseIntError,
+}
+
+impl fmt::Display for ParsePositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid position \"{}\": {}", self.position, self.err)
+    }
+}
+
+fn parse_positions_line(s: &str) -> Result<BTreeMap<u16, u32>, ParsePositionError> {
+    let poses = s.split(',').map(|n| {
+        n.parse::<u16>().map_err(|err| ParsePositionError {
+            position: n.to_owned(),
+            err,
+        })
+    });
 
     let mut num_by_pos = BTreeMap::new();
 
@@ -17,26 +37,85 @@ fn parse_positions_line(s: &str) -> Result<BTreeMap<u16, u32>, ParseIntError> {
     Ok(num_by_pos)
 }
 
+fn constant_cost(pos_delta: u32) -> u64 {
+    pos_delta as u64
+}
+
+fn increasing_cost(pos_delta: u32) -> u64 {
+    let d = pos_delta as u64;
+    d * (d + 1) / 2
+}
+
+/// Scans every position from the min to the max position. Works for any cost
+/// function, but see `find_min_constant_cost_position` and
+/// `find_min_increasing_cost_position` for the specific ones.
+#[cfg(test)]
 fn find_min_cost_position<F>(num_by_pos: &BTreeMap<u16, u32>, cost_fn: F) -> Option<(u16, u64)>
 where
     F: Fn(u32) -> u64,
+{
+    find_min_directed_cost_position(num_by_pos, |src_pos, dst_pos| {
+        cost_fn(((dst_pos as i32) - (src_pos as i32)).unsigned_abs())
+    })
+}
+
+/// Considers only the destination positions within `range`. Returns
+/// `None` if the range or `num_by_pos` is empty.
+fn find_min_cost_position_in<F>(
+    num_by_pos: &BTreeMap<u16, u32>,
+    cost_fn: F,
+    range: RangeInclusive<u16>,
+) -> Option<(u16, u64)>
+where
+    F: Fn(u32) -> u64,
+{
+    find_min_directed_cost_position_in(
+        num_by_pos,
+        |src_pos, dst_pos| cost_fn(((dst_pos as i32) - (src_pos as i32)).unsigned_abs()),
+        range,
+    )
+}
+
+/// Like `find_min_cost_position`, but `cost_fn` receives the source and
+/// destination positions, allowing costs depending on the direction of
+/// movement.
+#[cfg(test)]
+fn find_min_directed_cost_position<F>(
+    num_by_pos: &BTreeMap<u16, u32>,
+    cost_fn: F,
+) -> Option<(u16, u64)>
+where
+    F: Fn(u16, u16) -> u64,
 {
     if num_by_pos.is_empty() {
         return None;
     }
 
-    let min_pos = 0;
+    let min_pos = *num_by_pos.first_key_value().unwrap().0;
     let max_pos = *num_by_pos.last_key_value().unwrap().0;
 
+    find_min_directed_cost_position_in(num_by_pos, cost_fn, min_pos..=max_pos)
+}
+
+fn find_min_directed_cost_position_in<F>(
+    num_by_pos: &BTreeMap<u16, u32>,
+    cost_fn: F,
+    range: RangeInclusive<u16>,
+) -> Option<(u16, u64)>
+where
+    F: Fn(u16, u16) -> u64,
+{
+    if num_by_pos.is_empty() {
+        return None;
+    }
+
     let mut min_cost_found: Option<(u16, u64)> = None;
 
-    for dst_pos in min_pos..=max_pos {
+    for dst_pos in range {
         let mut cost: u64 = 0;
 
         for (&src_pos, &num) in num_by_pos {
-            let pos_delta = ((dst_pos as i32) - (src_pos as i32)).unsigned_abs();
-
-            cost += (num as u64) * cost_fn(pos_delta);
+            cost += (num as u64) * cost_fn(src_pos, dst_pos);
 
             if let Some((_, cost_found)) = min_cost_found {
                 if cost > cost_found {
@@ -60,38 +139,174 @@ where
     min_cost_found
 }
 
+/// With constant cost, the weighted median of the positions is optimal.
+fn find_min_constant_cost_position(num_by_pos: &BTreeMap<u16, u32>) -> Option<(u16, u64)> {
+    let num_total: u64 = num_by_pos.values().map(|&n| n as u64).sum();
+    let mut num_seen: u64 = 0;
+
+    for (&pos, &num) in num_by_pos {
+        num_seen += num as u64;
+        if num_seen * 2 >= num_total {
+            return find_min_cost_position_in(num_by_pos, constant_cost, pos..=pos);
+        }
+    }
+
+    None
+}
+
+/// With increasing cost, the optimal position is within 1 of the weighted
+/// mean of the positions, so it's enough to check the positions around
+/// it.
+fn find_min_increasing_cost_position(num_by_pos: &BTreeMap<u16, u32>) -> Option<(u16, u64)> {
+    let (num_total, pos_total) =
+        num_by_pos
+            .iter()
+            .fold((0u64, 0u64), |(num_total, pos_total), (&pos, &num)| {
+                (num_total + num as u64, pos_total + pos as u64 * num as u64)
+            });
+
+    if num_total == 0 {
+        return None;
+    }
+
+    let mean = (pos_total / num_total) as u16;
+
+    find_min_cost_position_in(
+        num_by_pos,
+        increasing_cost,
+        mean.saturating_sub(1)..=mean.saturating_add(2),
+    )
+}
+
+/// Returns the min cost positions and their costs with constant and
+/// increasing cost functions, or `None` if there are no positions.
+fn solve(num_by_pos: &BTreeMap<u16, u32>) -> Option<((u16, u64), (u16, u64))> {
+    let constant = find_min_constant_cost_position(num_by_pos)?;
+    let increasing = find_min_increasing_cost_position(num_by_pos)?;
+    Some((constant, increasing))
+}
+
 /// CLI usage: cargo run -- input.txt
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
-    let num_by_pos: BTreeMap<u16, u32> =
-        io::BufReader::new(File::open(filename).expect("File not found"))
-            .lines()
-            .next()
-            .expect("File is empty")
-            .map(|l| {
-                parse_positions_line(&l)
-                    .unwrap_or_else(|err| panic!("Invalid positions line: {}", err))
-            })
-            .expect("Line not UTF-8");
-
-    let cost_fns: [(&str, &dyn Fn(u32) -> u64); 2] = [
-        ("constant", &|d| d as u64),
-        ("increasing", &|d| {
-            let mut sum = 0;
-            for s in 1..=d {
-                sum += s;
-            }
-            sum as u64
-        }),
-    ];
+    let line = io::BufReader::new(File::open(filename).expect("File not found"))
+        .lines()
+        .next()
+        .expect("File is empty")
+        .expect("Line not UTF-8");
+
+    let num_by_pos = match parse_positions_line(&line) {
+        Ok(num_by_pos) => num_by_pos,
+        Err(err) => {
+            eprintln!("Invalid positions line: {}", err);
+            process::exit(1);
+        }
+    };
 
-    for (cost_fn_desc, cost_fn) in cost_fns {
-        let (pos, cost) = find_min_cost_position(&num_by_pos, cost_fn).unwrap();
+    let (constant, increasing) = match solve(&num_by_pos) {
+        Some(solution) => solution,
+        None => {
+            eprintln!("No positions");
+            process::exit(1);
+        }
+    };
 
+    for (cost_fn_desc, (pos, cost)) in [("constant", constant), ("increasing", increasing)] {
         println!(
             "min cost position when {} cost fn: pos={}, cost={}",
             cost_fn_desc, pos, cost
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "16,1,2,0,4,2,7,1,2,14";
+
+    #[test]
+    fn find_min_constant_cost_position_matches_scan() {
+        let num_by_pos = parse_positions_line(EXAMPLE).unwrap();
+        assert_eq!(find_min_constant_cost_position(&num_by_pos), Some((2, 37)));
+        assert_eq!(
+            find_min_cost_position(&num_by_pos, constant_cost),
+            Some((2, 37))
+        );
+    }
+
+    #[test]
+    fn find_min_increasing_cost_position_matches_scan() {
+        let num_by_pos = parse_positions_line(EXAMPLE).unwrap();
+        assert_eq!(
+            find_min_increasing_cost_position(&num_by_pos),
+            Some((5, 168))
+        );
+        assert_eq!(
+            find_min_cost_position(&num_by_pos, increasing_cost),
+            Some((5, 168))
+        );
+    }
+
+    #[test]
+    fn solve_example() {
+        let num_by_pos = parse_positions_line(EXAMPLE).unwrap();
+        assert_eq!(solve(&num_by_pos), Some(((2, 37), (5, 168))));
+        assert_eq!(solve(&BTreeMap::new()), None);
+    }
+
+    #[test]
+    fn increasing_cost_is_triangular() {
+        assert_eq!(increasing_cost(0), 0);
+        assert_eq!(increasing_cost(1), 1);
+        assert_eq!(increasing_cost(11), 66);
+    }
+
+    #[test]
+    fn parse_positions_line_when_invalid() {
+        assert_eq!(
+            parse_positions_line("16,1,x").unwrap_err().to_string(),
+            "Invalid position \"x\": invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn find_min_cost_position_in_range() {
+        let num_by_pos = parse_positions_line(EXAMPLE).unwrap();
+        assert_eq!(
+            find_min_cost_position_in(&num_by_pos, constant_cost, 10..=20),
+            Some((10, 71))
+        );
+        assert_eq!(
+            find_min_cost_position_in(&num_by_pos, constant_cost, 0..=20),
+            Some((2, 37))
+        );
+        assert_eq!(
+            find_min_cost_position_in(&num_by_pos, constant_cost, RangeInclusive::new(5, 4)),
+            None
+        );
+    }
+
+    #[test]
+    fn find_min_directed_cost_position_when_asymmetric() {
+        let num_by_pos = parse_positions_line(EXAMPLE).unwrap();
+        let moving_right_costs_triple = |src_pos: u16, dst_pos: u16| {
+            if dst_pos > src_pos {
+                3 * (dst_pos - src_pos) as u64
+            } else {
+                (src_pos - dst_pos) as u64
+            }
+        };
+        assert_eq!(
+            find_min_directed_cost_position(&num_by_pos, moving_right_costs_triple),
+            Some((1, 43))
+        );
+        assert_eq!(
+            find_min_directed_cost_position(&num_by_pos, |src_pos, dst_pos| {
+                constant_cost(((dst_pos as i32) - (src_pos as i32)).unsigned_abs())
+            }),
+            Some((2, 37))
+        );
+    }
+}
@@ -1,11 +1,12 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::env;
 use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::process;
 use std::result::Result;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 enum ParseSignalsError {
     Patterns(usize),
     Outputs(usize),
@@ -27,6 +28,39 @@ impl fmt::Display for ParseSignalsError {
     }
 }
 
+#[derive(Debug)]
+enum DecipherError {
+    InvalidChar(char),
+    MissingLength(usize),
+    Ambiguous(u8),
+    UnrecognizedOutput(String),
+    InvalidSignals(ParseSignalsError),
+    Io {
+        line_num: usize,
+        err: io::Error,
+    },
+    /// The error at a 1-based line number of the input
+    Line {
+        line_num: usize,
+        err: Box<DecipherError>,
+    },
+}
+
+impl fmt::Display for DecipherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use DecipherError::*;
+        match *self {
+            InvalidChar(c) => write!(f, "Invalid segment character ({})", c),
+            MissingLength(n) => write!(f, "Missing signal pattern with {} segments", n),
+            Ambiguous(d) => write!(f, "Ambiguous signal patterns for digit {}", d),
+            UnrecognizedOutput(ref o) => write!(f, "Unrecognized signal output: {}", o),
+            InvalidSignals(ref err) => write!(f, "Invalid signals: {}", err),
+            Io { line_num, ref err } => write!(f, "Cannot read line {}: {}", line_num, err),
+            Line { line_num, ref err } => write!(f, "Line {}: {}", line_num, err),
+        }
+    }
+}
+
 const NUM_SIGNAL_PATTERNS: usize = 10;
 const NUM_SIGNAL_OUTPUTS: usize = 4;
 
@@ -37,11 +71,17 @@ struct Signals<'a> {
 }
 
 impl<'a> Signals<'a> {
-    fn decipher(self: &Signals<'a>) -> Option<u16> {
+    fn decipher(self: &Signals<'a>) -> Result<u16, DecipherError> {
+        let digits = self.decipher_digits()?;
+        Ok(digits.iter().fold(0, |acc, &d| acc * 10 + d as u16))
+    }
+
+    /// Returns the output digits, the most significant digit first.
+    fn decipher_digits(self: &Signals<'a>) -> Result<[u8; NUM_SIGNAL_OUTPUTS], DecipherError> {
         SignalPatterns::parse_patterns(self.patterns)?.parse_outputs(self.outputs)
     }
 
-    fn parse(line: &str) -> Result<Signals, ParseSignalsError> {
+    fn parse(line: &str) -> Result<Signals<'_>, ParseSignalsError> {
         let mut patterns: Vec<&str> = vec![];
         let mut outputs: Vec<&str> = vec![];
         let mut read_outputs = false;
@@ -78,10 +118,14 @@ impl<'a> Signals<'a> {
 #[derive(Debug)]
 struct SignalPatterns {
     chars_of_0: HashSet<char>,
+    chars_of_1: HashSet<char>,
     chars_of_2: HashSet<char>,
     chars_of_3: HashSet<char>,
+    chars_of_4: HashSet<char>,
     chars_of_5: HashSet<char>,
     chars_of_6: HashSet<char>,
+    chars_of_7: HashSet<char>,
+    chars_of_8: HashSet<char>,
     chars_of_9: HashSet<char>,
 }
 
@@ -108,24 +152,33 @@ impl SignalPatterns {
     /// Loop 3:
     ///   5: has 5 chars && difference to the pattern of 9 leaves 0 chars
     ///   2: has 5 chars && difference to the pattern of 9 leaves 1 char
-    fn parse_patterns(patterns: [&str; NUM_SIGNAL_PATTERNS]) -> Option<SignalPatterns> {
+    fn parse_patterns(
+        patterns: [&str; NUM_SIGNAL_PATTERNS],
+    ) -> Result<SignalPatterns, DecipherError> {
+        for pat in patterns {
+            check_segment_chars(pat)?;
+        }
+
         let mut opt_chars_of_1: Option<HashSet<char>> = None;
         let mut opt_chars_of_4: Option<HashSet<char>> = None;
+        let mut opt_chars_of_7: Option<HashSet<char>> = None;
+        let mut opt_chars_of_8: Option<HashSet<char>> = None;
 
         for pat in patterns {
+            let cs: HashSet<char> = pat.chars().collect();
             match pat.len() {
-                2 => {
-                    opt_chars_of_1 = Some(pat.chars().collect());
-                }
-                4 => {
-                    opt_chars_of_4 = Some(pat.chars().collect());
-                }
+                2 => set_deduced(&mut opt_chars_of_1, cs, 1)?,
+                3 => set_deduced(&mut opt_chars_of_7, cs, 7)?,
+                4 => set_deduced(&mut opt_chars_of_4, cs, 4)?,
+                7 => set_deduced(&mut opt_chars_of_8, cs, 8)?,
                 _ => {}
             }
         }
 
-        let chars_of_1 = opt_chars_of_1?;
-        let chars_of_4 = opt_chars_of_4?;
+        let chars_of_1 = opt_chars_of_1.ok_or(DecipherError::MissingLength(2))?;
+        let chars_of_4 = opt_chars_of_4.ok_or(DecipherError::MissingLength(4))?;
+        let chars_of_7 = opt_chars_of_7.ok_or(DecipherError::MissingLength(3))?;
+        let chars_of_8 = opt_chars_of_8.ok_or(DecipherError::MissingLength(7))?;
 
         let mut opt_chars_of_0: Option<HashSet<char>> = None;
         let mut opt_chars_of_3: Option<HashSet<char>> = None;
@@ -137,27 +190,27 @@ impl SignalPatterns {
                 5 => {
                     let cs: HashSet<char> = pat.chars().collect();
                     if cs.intersection(&chars_of_1).count() == 2 {
-                        opt_chars_of_3 = Some(cs);
+                        set_deduced(&mut opt_chars_of_3, cs, 3)?;
                     }
                 }
                 6 => {
                     let cs: HashSet<char> = pat.chars().collect();
                     if cs.intersection(&chars_of_4).count() == 4 {
-                        opt_chars_of_9 = Some(cs);
+                        set_deduced(&mut opt_chars_of_9, cs, 9)?;
                     } else if cs.intersection(&chars_of_1).count() == 2 {
-                        opt_chars_of_0 = Some(cs);
+                        set_deduced(&mut opt_chars_of_0, cs, 0)?;
                     } else {
-                        opt_chars_of_6 = Some(cs);
+                        set_deduced(&mut opt_chars_of_6, cs, 6)?;
                     }
                 }
                 _ => {}
             }
         }
 
-        let chars_of_3 = opt_chars_of_3?;
-        let chars_of_9 = opt_chars_of_9?;
-        let chars_of_0 = opt_chars_of_0?;
-        let chars_of_6 = opt_chars_of_6?;
+        let chars_of_3 = opt_chars_of_3.ok_or(DecipherError::MissingLength(5))?;
+        let chars_of_9 = opt_chars_of_9.ok_or(DecipherError::MissingLength(6))?;
+        let chars_of_0 = opt_chars_of_0.ok_or(DecipherError::MissingLength(6))?;
+        let chars_of_6 = opt_chars_of_6.ok_or(DecipherError::MissingLength(6))?;
 
         let mut opt_chars_of_5: Option<HashSet<char>> = None;
         let mut opt_chars_of_2: Option<HashSet<char>> = None;
@@ -168,70 +221,141 @@ impl SignalPatterns {
                 continue; // handled already
             }
             match cs.difference(&chars_of_9).count() {
-                0 => {
-                    opt_chars_of_5 = Some(cs);
-                }
-                1 => {
-                    opt_chars_of_2 = Some(cs);
-                }
+                0 => set_deduced(&mut opt_chars_of_5, cs, 5)?,
+                1 => set_deduced(&mut opt_chars_of_2, cs, 2)?,
                 _ => {}
             }
         }
 
-        let chars_of_5 = opt_chars_of_5?;
-        let chars_of_2 = opt_chars_of_2?;
+        let chars_of_5 = opt_chars_of_5.ok_or(DecipherError::MissingLength(5))?;
+        let chars_of_2 = opt_chars_of_2.ok_or(DecipherError::MissingLength(5))?;
 
-        Some(SignalPatterns {
+        Ok(SignalPatterns {
             chars_of_0,
+            chars_of_1,
             chars_of_2,
             chars_of_3,
+            chars_of_4,
             chars_of_5,
             chars_of_6,
+            chars_of_7,
+            chars_of_8,
             chars_of_9,
         })
     }
 
-    fn parse_outputs(self: &SignalPatterns, outputs: [&str; NUM_SIGNAL_OUTPUTS]) -> Option<u16> {
-        let n3 = self.output_token_to_digit(outputs[0])?;
-        let n2 = self.output_token_to_digit(outputs[1])?;
-        let n1 = self.output_token_to_digit(outputs[2])?;
-        let n0 = self.output_token_to_digit(outputs[3])?;
-        Some(n0 + 10 * n1 + 100 * n2 + 1000 * n3)
+    /// Returns the deduced pattern of each digit, indexed by the digit.
+    fn digit_patterns(self: &SignalPatterns) -> [BTreeSet<char>; 10] {
+        [
+            &self.chars_of_0,
+            &self.chars_of_1,
+            &self.chars_of_2,
+            &self.chars_of_3,
+            &self.chars_of_4,
+            &self.chars_of_5,
+            &self.chars_of_6,
+            &self.chars_of_7,
+            &self.chars_of_8,
+            &self.chars_of_9,
+        ]
+        .map(|cs| cs.iter().copied().collect())
+    }
+
+    fn parse_outputs(
+        self: &SignalPatterns,
+        outputs: [&str; NUM_SIGNAL_OUTPUTS],
+    ) -> Result<[u8; NUM_SIGNAL_OUTPUTS], DecipherError> {
+        let mut digits = [0; NUM_SIGNAL_OUTPUTS];
+        for (digit, token) in digits.iter_mut().zip(outputs) {
+            check_segment_chars(token)?;
+            *digit = self
+                .output_token_to_digit(token)
+                .ok_or_else(|| DecipherError::UnrecognizedOutput(token.to_owned()))?;
+        }
+        Ok(digits)
     }
 
-    fn output_token_to_digit(self: &SignalPatterns, token: &str) -> Option<u16> {
+    fn output_token_to_digit(self: &SignalPatterns, token: &str) -> Option<u8> {
         match token.len() {
             2 => Some(1),
             3 => Some(7),
             4 => Some(4),
             7 => Some(8),
             _ => {
-                let cs: HashSet<char> = token.chars().collect();
-                if cs == self.chars_of_0 {
-                    Some(0)
-                } else if cs == self.chars_of_2 {
-                    Some(2)
-                } else if cs == self.chars_of_3 {
-                    Some(3)
-                } else if cs == self.chars_of_5 {
-                    Some(5)
-                } else if cs == self.chars_of_6 {
-                    Some(6)
-                } else if cs == self.chars_of_9 {
-                    Some(9)
-                } else {
-                    None
-                }
+                let cs: BTreeSet<char> = token.chars().collect();
+                self.digit_patterns()
+                    .iter()
+                    .position(|p| *p == cs)
+                    .map(|d| d as u8)
             }
         }
     }
 }
 
+/// The segments of each digit, indexed by the digit, when the segments are
+/// wired as intended
+#[cfg(test)]
+const CANONICAL_DIGIT_SEGMENTS: [&str; 10] = [
+    "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+];
+
+/// Returns the scrambled pattern of a digit, where `mapping` gives the
+/// wire of each segment from `a` to `g`.
+#[cfg(test)]
+fn encode_digit(mapping: &[char; 7], digit: u8) -> String {
+    CANONICAL_DIGIT_SEGMENTS[digit as usize]
+        .chars()
+        .map(|c| mapping[(c as u8 - b'a') as usize])
+        .collect()
+}
+
+/// The inverse of deciphering the outputs: returns the scrambled pattern
+/// of each digit of `value`, padded with leading zeros.
+///
+/// Panics if `value` has more digits than there are outputs.
+#[cfg(test)]
+fn encode_number(mapping: &[char; 7], value: u16) -> [String; NUM_SIGNAL_OUTPUTS] {
+    assert!(value <= 9999, "Too many digits in value: {}", value);
+
+    let mut n = value;
+    let mut patterns: [String; NUM_SIGNAL_OUTPUTS] = Default::default();
+
+    for pattern in patterns.iter_mut().rev() {
+        *pattern = encode_digit(mapping, (n % 10) as u8);
+        n /= 10;
+    }
+
+    patterns
+}
+
+fn check_segment_chars(pattern: &str) -> Result<(), DecipherError> {
+    match pattern.chars().find(|c| !('a'..='g').contains(c)) {
+        Some(c) => Err(DecipherError::InvalidChar(c)),
+        None => Ok(()),
+    }
+}
+
+/// Sets the pattern deduced for a digit, unless another pattern has been
+/// deduced for it already.
+fn set_deduced(
+    chars_of_digit: &mut Option<HashSet<char>>,
+    cs: HashSet<char>,
+    digit: u8,
+) -> Result<(), DecipherError> {
+    match chars_of_digit {
+        Some(prev) if *prev != cs => Err(DecipherError::Ambiguous(digit)),
+        _ => {
+            *chars_of_digit = Some(cs);
+            Ok(())
+        }
+    }
+}
+
 fn count_digits(digits: &[u8], mut n: u16) -> u16 {
     let mut count = 0;
     while n > 0 {
         let x = (n % 10) as u8;
-        if digits.iter().any(|d| x == *d) {
+        if digits.contains(&x) {
             count += 1;
         }
         n /= 10;
@@ -239,39 +363,48 @@ fn count_digits(digits: &[u8], mut n: u16) -> u16 {
     count
 }
 
+/// Returns the number of 1, 4, 7, and 8 digits in the outputs, and the sum
+/// of the output values.
+fn solve(reader: impl BufRead) -> Result<(u32, u32), DecipherError> {
+    let digits = [1, 4, 7, 8];
+    let mut num_digits: u32 = 0;
+    let mut sum: u32 = 0;
+
+    for (idx, l) in reader.lines().enumerate() {
+        let line_num = idx + 1;
+
+        let line = l.map_err(|err| DecipherError::Io { line_num, err })?;
+
+        let output = Signals::parse(&line)
+            .map_err(DecipherError::InvalidSignals)
+            .and_then(|signals| signals.decipher())
+            .map_err(|err| DecipherError::Line {
+                line_num,
+                err: Box::new(err),
+            })?;
+
+        num_digits += count_digits(&digits, output) as u32;
+        sum += output as u32;
+    }
+
+    Ok((num_digits, sum))
+}
+
 /// CLI usage: cargo run -- input.txt
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
-    let signal_outputs: Vec<u16> =
-        io::BufReader::new(File::open(filename).expect("File not found"))
-            .lines()
-            .map(|l| {
-                let line = l.expect("Line not UTF-8");
-                let signals = Signals::parse(&line)
-                    .unwrap_or_else(|err| panic!("Invalid signal output line: {}", err));
-                signals
-                    .decipher()
-                    .unwrap_or_else(|| panic!("Unrecognized signal pattern: {}", line))
-            })
-            .collect();
-
-    {
-        let digits = vec![1, 4, 7, 8];
-        println!(
-            "num digits {:?}: {}",
-            &digits,
-            signal_outputs
-                .iter()
-                .map(|n| count_digits(&digits, *n))
-                .sum::<u16>()
-        );
-    }
+    let (num_digits, sum) = solve(io::BufReader::new(
+        File::open(filename).expect("File not found"),
+    ))
+    .unwrap_or_else(|err| {
+        eprintln!("Cannot decipher: {}", err);
+        process::exit(1);
+    });
+
+    println!("num digits [1, 4, 7, 8]: {}", num_digits);
 
-    println!(
-        "sum: {}",
-        signal_outputs.iter().map(|o| *o as u32).sum::<u32>()
-    );
+    println!("sum: {}", sum);
 }
 
 #[cfg(test)]
@@ -283,6 +416,100 @@ mod tests {
         let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab |
 cdfeb fcadb cdfeb cdbaf";
         let s = Signals::parse(line).unwrap();
-        assert_eq!(s.decipher(), Some(5353));
+        assert_eq!(s.decipher().unwrap(), 5353);
+    }
+
+    #[test]
+    fn test_decipher_digits() {
+        let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab |
+cdfeb fcadb cdfeb cdbaf";
+        let s = Signals::parse(line).unwrap();
+        assert_eq!(s.decipher_digits().unwrap(), [5, 3, 5, 3]);
+    }
+
+    #[test]
+    fn test_digit_patterns() {
+        let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab |
+cdfeb fcadb cdfeb cdbaf";
+        let s = Signals::parse(line).unwrap();
+        let patterns = SignalPatterns::parse_patterns(s.patterns)
+            .unwrap()
+            .digit_patterns();
+        assert_eq!(patterns[8].len(), 7);
+        assert_eq!(patterns[7].difference(&patterns[1]).count(), 1);
+        assert!(patterns[1].is_subset(&patterns[7]));
+        assert_eq!(patterns[5], "cdfbe".chars().collect());
+    }
+
+    #[test]
+    fn test_decipher_invalid_char() {
+        let line = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ah |
+cdfeb fcadb cdfeb cdbaf";
+        let s = Signals::parse(line).unwrap();
+        assert!(matches!(s.decipher(), Err(DecipherError::InvalidChar('h'))));
+    }
+
+    const EXAMPLE: &str = "\
+be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
+edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
+fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
+fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
+aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
+fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
+dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
+bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
+egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
+gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
+";
+
+    #[test]
+    fn solve_example() {
+        assert_eq!(solve(EXAMPLE.as_bytes()).unwrap(), (26, 61229));
+    }
+
+    #[test]
+    fn solve_when_invalid_line() {
+        let input =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf
+acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ah | cdfeb fcadb cdfeb cdbaf
+";
+        let err = solve(input.as_bytes()).unwrap_err();
+        match err {
+            DecipherError::Line { line_num, ref err } => {
+                assert_eq!(line_num, 2);
+                assert!(matches!(**err, DecipherError::InvalidChar('h')));
+            }
+            ref err => panic!("Unexpected error: {:?}", err),
+        }
+        assert_eq!(err.to_string(), "Line 2: Invalid segment character (h)");
+    }
+
+    #[test]
+    fn solve_when_not_utf8() {
+        let input: &[u8] = b"\xff\n";
+        match solve(input) {
+            Err(err @ DecipherError::Io { line_num: 1, .. }) => {
+                assert!(err.to_string().starts_with("Cannot read line 1: "))
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn encode_number_round_trip() {
+        let mapping = ['d', 'e', 'a', 'f', 'g', 'b', 'c'];
+
+        let patterns: Vec<String> = (0..10).map(|d| encode_digit(&mapping, d)).collect();
+
+        for value in [5353, 42, 9870, 0] {
+            let line = format!(
+                "{} | {}",
+                patterns.join(" "),
+                encode_number(&mapping, value).join(" ")
+            );
+            assert_eq!(Signals::parse(&line).unwrap().decipher().unwrap(), value);
+        }
+
+        assert_eq!(encode_number(&mapping, 42)[..2], ["deagbc", "deagbc"]);
     }
 }
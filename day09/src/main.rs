@@ -1,15 +1,45 @@
 use bitvec::prelude as bv;
-use std::cmp::Ordering;
-use std::collections::{BTreeSet, VecDeque};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, BinaryHeap, VecDeque};
 use std::env;
 use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::ops::Index;
+use std::process;
+
+const DEFAULT_MAX_BASIN_HEIGHT: u8 = 9;
+
+const POINT_NEIGHBOURS_4: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+const POINT_NEIGHBOURS_8: [(isize, isize); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// Which points are adjacent to each other: either only orthogonally
+/// (`Four`), or diagonally, too (`Eight`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Connectivity {
+    #[default]
+    Four,
+    Eight,
+}
 
-const MAX_BASIN_HEIGHT: u8 = 9;
-
-const POINT_NEIGHBOURS: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+impl Connectivity {
+    fn neighbours(self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &POINT_NEIGHBOURS_4,
+            Connectivity::Eight => &POINT_NEIGHBOURS_8,
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 struct Point {
@@ -26,8 +56,9 @@ impl Point {
         self.y * (max_x + 1) + self.x
     }
 
-    fn adjacent_points(self: &Point, max: &Point) -> Vec<Point> {
-        POINT_NEIGHBOURS
+    fn adjacent_points(self: &Point, max: &Point, connectivity: Connectivity) -> Vec<Point> {
+        connectivity
+            .neighbours()
             .iter()
             .flat_map(|(dx, dy)| {
                 match (
@@ -75,9 +106,12 @@ impl PartialEq for LowPoint {
     }
 }
 
+/// Orders by row, then by column
 impl Ord for LowPoint {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.0.point.cmp(&other.0.point)
+        let Point { x: sx, y: sy } = self.0.point;
+        let Point { x: ox, y: oy } = other.0.point;
+        (sy, sx).cmp(&(oy, ox))
     }
 }
 
@@ -95,7 +129,14 @@ impl fmt::Display for HeightPoint {
 
 #[derive(Debug)]
 enum ParseHeightmapError {
-    InvalidLine(String),
+    InvalidLine {
+        index: usize,
+        line: String,
+    },
+    Io {
+        index: usize,
+        err: io::Error,
+    },
     UnexpectedLineLength {
         index: usize,
         expected: usize,
@@ -107,7 +148,10 @@ impl fmt::Display for ParseHeightmapError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use ParseHeightmapError::*;
         match *self {
-            InvalidLine(ref line) => write!(f, "Invalid height line: {}", line),
+            InvalidLine { index, ref line } => {
+                write!(f, "Invalid height line at {}: {}", index, line)
+            }
+            Io { index, ref err } => write!(f, "Cannot read line at {}: {}", index, err),
             UnexpectedLineLength {
                 index,
                 expected,
@@ -124,9 +168,54 @@ impl fmt::Display for ParseHeightmapError {
 #[derive(Debug)]
 struct Heightmap {
     data: Vec<Vec<u8>>,
+    /// Points at this height or higher don't belong to any basin
+    max_basin_height: u8,
 }
 
 impl Heightmap {
+    /// Reads rows of height digits. Line indexes in errors are 0-based.
+    fn read(reader: impl BufRead) -> Result<Heightmap, ParseHeightmapError> {
+        let data = reader
+            .lines()
+            .enumerate()
+            .map(|(index, l)| {
+                let line = l.map_err(|err| ParseHeightmapError::Io { index, err })?;
+                parse_height_line(&line).ok_or(ParseHeightmapError::InvalidLine { index, line })
+            })
+            .collect::<Result<Vec<Vec<u8>>, ParseHeightmapError>>()?;
+
+        data.try_into()
+    }
+
+    /// Parses rows of whitespace separated heights, allowing heights
+    /// above 9.
+    #[cfg(test)]
+    fn from_whitespace_separated(value: &str) -> Result<Heightmap, ParseHeightmapError> {
+        let data = value
+            .lines()
+            .enumerate()
+            .map(|(index, l)| {
+                l.split_ascii_whitespace()
+                    .map(|t| t.parse::<u8>().ok())
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(|| ParseHeightmapError::InvalidLine {
+                        index,
+                        line: l.into(),
+                    })
+            })
+            .collect::<Result<Vec<Vec<u8>>, ParseHeightmapError>>()?;
+
+        data.try_into()
+    }
+
+    #[cfg(test)]
+    fn with_max_basin_height(self, max_basin_height: u8) -> Heightmap {
+        Heightmap {
+            max_basin_height,
+            ..self
+        }
+    }
+
     fn max_point(&self) -> Point {
         Point {
             x: self.data[0].len() - 1,
@@ -134,7 +223,10 @@ impl Heightmap {
         }
     }
 
-    fn collect_low_points(self: &Heightmap) -> Vec<HeightPoint> {
+    /// Returns the low points sorted by row, then by column, regardless of
+    /// the order the map is scanned. A plateau of equal heights lower than
+    /// its surroundings yields all of its points.
+    fn collect_low_points(self: &Heightmap, connectivity: Connectivity) -> Vec<HeightPoint> {
         let mut low_points = BTreeSet::<LowPoint>::new();
 
         if self.data.is_empty() {
@@ -157,7 +249,7 @@ impl Heightmap {
             // println!("C: {}@{}", height, &candidate_point);
 
             let mut adjacent_points_to_check: VecDeque<Point> = candidate_point
-                .adjacent_points(&max_point)
+                .adjacent_points(&max_point, connectivity)
                 .into_iter()
                 .collect();
 
@@ -174,7 +266,7 @@ impl Heightmap {
                         points_visited.set(adjacent_point.index1d(max_point.x), true);
 
                         let aps: Vec<Point> = adjacent_point
-                            .adjacent_points(&max_point)
+                            .adjacent_points(&max_point, connectivity)
                             .into_iter()
                             .filter(|p| {
                                 !points_visited[p.index1d(max_point.x)]
@@ -211,7 +303,20 @@ impl Heightmap {
         low_points.into_iter().map(|p| p.0).collect()
     }
 
-    fn collect_basin(&self, low_point: &Point) -> Vec<Point> {
+    /// Returns the heights of the low points, in the same order as
+    /// `collect_low_points`.
+    #[cfg(test)]
+    fn low_point_heights(&self, connectivity: Connectivity) -> Vec<u8> {
+        self.collect_low_points(connectivity)
+            .into_iter()
+            .map(|hp| hp.height)
+            .collect()
+    }
+
+    /// Collects the points of the basin containing `low_point`. To find all
+    /// the basins, `label_basins` is faster.
+    #[cfg(test)]
+    fn collect_basin(&self, low_point: &Point, connectivity: Connectivity) -> Vec<Point> {
         let mut basin_points = Vec::<Point>::new();
 
         if self.data.is_empty() {
@@ -231,11 +336,11 @@ impl Heightmap {
 
             points_visited.set(candidate_point.index1d(max_point.x), true);
 
-            if height < MAX_BASIN_HEIGHT {
+            if height < self.max_basin_height {
                 // println!("B: {}@{}", height, &candidate_point);
 
                 let cps: Vec<Point> = candidate_point
-                    .adjacent_points(&max_point)
+                    .adjacent_points(&max_point, connectivity)
                     .into_iter()
                     .filter(|p| {
                         !points_visited[p.index1d(max_point.x)]
@@ -251,6 +356,218 @@ impl Heightmap {
 
         basin_points
     }
+
+    /// Renders the heights, one row per line, bracketing the heights of
+    /// the points in `highlight`.
+    #[cfg(test)]
+    fn render(&self, highlight: &[Point]) -> String {
+        let mut out = String::new();
+
+        for (y, row) in self.data.iter().enumerate() {
+            let line: String = row
+                .iter()
+                .enumerate()
+                .map(|(x, h)| {
+                    if highlight.contains(&Point::new(x, y)) {
+                        format!("[{}]", h)
+                    } else {
+                        format!(" {} ", h)
+                    }
+                })
+                .collect();
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// Partitions every point below the max basin height to exactly one
+    /// basin by flooding from the low points, lowest heights first, and
+    /// equal heights by row, then by column.
+    /// Adjacent low points of equal height form a single basin. Basin ids
+    /// follow the order of `collect_low_points`. Returns the points sorted
+    /// by row, then by column.
+    fn watershed(&self, connectivity: Connectivity) -> Vec<(Point, u32)> {
+        let mut labels: Vec<Vec<Option<u32>>> =
+            self.data.iter().map(|row| vec![None; row.len()]).collect();
+
+        if self.data.is_empty() {
+            return vec![];
+        }
+
+        let max_point = self.max_point();
+
+        let mut flood_queue: BinaryHeap<Reverse<(u8, usize, usize)>> = BinaryHeap::new();
+
+        let mut next_id = 0;
+
+        for HeightPoint { height, point } in self.collect_low_points(connectivity) {
+            if height >= self.max_basin_height || labels[point.y][point.x].is_some() {
+                continue;
+            }
+
+            let mut plateau_points: VecDeque<Point> = VecDeque::new();
+
+            labels[point.y][point.x] = Some(next_id);
+            plateau_points.push_back(point);
+
+            while let Some(p) = plateau_points.pop_front() {
+                flood_queue.push(Reverse((height, p.y, p.x)));
+
+                for ap in p.adjacent_points(&max_point, connectivity) {
+                    if labels[ap.y][ap.x].is_none() && self[&ap] == height {
+                        labels[ap.y][ap.x] = Some(next_id);
+                        plateau_points.push_back(ap);
+                    }
+                }
+            }
+
+            next_id += 1;
+        }
+
+        while let Some(Reverse((_, y, x))) = flood_queue.pop() {
+            let id = labels[y][x];
+
+            for ap in Point::new(x, y).adjacent_points(&max_point, connectivity) {
+                let h = self[&ap];
+                if labels[ap.y][ap.x].is_none() && h < self.max_basin_height {
+                    labels[ap.y][ap.x] = id;
+                    flood_queue.push(Reverse((h, ap.y, ap.x)));
+                }
+            }
+        }
+
+        labels
+            .into_iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.into_iter()
+                    .enumerate()
+                    .filter_map(move |(x, id)| id.map(|id| (Point::new(x, y), id)))
+            })
+            .collect()
+    }
+
+    /// Returns the low point and the size of the `n` largest basins, the
+    /// largest first. Basins of equal size are ordered by the position of
+    /// their low point, by row, then by column. The low point of a basin is
+    /// its lowest point, with equal heights ordered the same way.
+    fn largest_basins(&self, n: usize, connectivity: Connectivity) -> Vec<(Point, usize)> {
+        let labels = self.label_basins(connectivity);
+        let mut low_points: Vec<HeightPoint> = vec![];
+
+        for (y, row) in labels.iter().enumerate() {
+            for (x, label) in row.iter().enumerate() {
+                let Some(id) = label else {
+                    continue;
+                };
+
+                let hp = HeightPoint::new(self.data[y][x], Point::new(x, y));
+                let idx = *id as usize;
+
+                if idx >= low_points.len() {
+                    low_points.push(hp);
+                    continue;
+                }
+
+                let low_point = &mut low_points[idx];
+
+                if (hp.height, hp.point.y, hp.point.x)
+                    < (low_point.height, low_point.point.y, low_point.point.x)
+                {
+                    *low_point = hp;
+                }
+            }
+        }
+
+        let mut basins: Vec<(HeightPoint, usize)> =
+            low_points.into_iter().zip(basin_sizes(&labels)).collect();
+
+        basins.sort_by_key(|(low_point, size)| {
+            (Reverse(*size), low_point.point.y, low_point.point.x)
+        });
+
+        basins
+            .into_iter()
+            .take(n)
+            .map(|(low_point, size)| (low_point.point, size))
+            .collect()
+    }
+
+    /// The product of the sizes of the `n` largest basins, see
+    /// `largest_basins`. If there are fewer than `n` basins, uses all of
+    /// them. Uses 4-connectivity.
+    #[cfg(test)]
+    fn largest_basin_product(&self, n: usize) -> u64 {
+        self.largest_basin_product_with(n, Connectivity::default())
+    }
+
+    /// Like `largest_basin_product`, but with the given connectivity.
+    fn largest_basin_product_with(&self, n: usize, connectivity: Connectivity) -> u64 {
+        self.largest_basins(n, connectivity)
+            .iter()
+            .map(|(_, size)| *size as u64)
+            .product()
+    }
+
+    /// Labels every point with the id of the basin it belongs to, with a
+    /// single pass over the map. Points at the max basin height don't
+    /// belong to any basin. Basin ids start from 0, in the order of the
+    /// first point of the basin found when scanning rows top-down.
+    fn label_basins(&self, connectivity: Connectivity) -> Vec<Vec<Option<u32>>> {
+        let mut labels: Vec<Vec<Option<u32>>> =
+            self.data.iter().map(|row| vec![None; row.len()]).collect();
+
+        if self.data.is_empty() {
+            return labels;
+        }
+
+        let max_point = self.max_point();
+
+        let mut next_id = 0;
+
+        for y in 0..=max_point.y {
+            for x in 0..=max_point.x {
+                if labels[y][x].is_some() || self.data[y][x] >= self.max_basin_height {
+                    continue;
+                }
+
+                let mut basin_point_candidates: VecDeque<Point> = VecDeque::new();
+
+                labels[y][x] = Some(next_id);
+                basin_point_candidates.push_back(Point::new(x, y));
+
+                while let Some(candidate_point) = basin_point_candidates.pop_front() {
+                    for p in candidate_point.adjacent_points(&max_point, connectivity) {
+                        if labels[p.y][p.x].is_none() && self[&p] < self.max_basin_height {
+                            labels[p.y][p.x] = Some(next_id);
+                            basin_point_candidates.push_back(p);
+                        }
+                    }
+                }
+
+                next_id += 1;
+            }
+        }
+
+        labels
+    }
+}
+
+/// Returns the number of points in each basin, indexed by basin id.
+fn basin_sizes(labels: &[Vec<Option<u32>>]) -> Vec<usize> {
+    let mut sizes: Vec<usize> = vec![];
+
+    for id in labels.iter().flatten().flatten() {
+        let idx = *id as usize;
+        if idx >= sizes.len() {
+            sizes.resize(idx + 1, 0);
+        }
+        sizes[idx] += 1;
+    }
+
+    sizes
 }
 
 impl Index<&Point> for Heightmap {
@@ -267,7 +584,13 @@ impl TryFrom<&str> for Heightmap {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         let data = value
             .lines()
-            .map(|l| parse_height_line(l).ok_or_else(|| ParseHeightmapError::InvalidLine(l.into())))
+            .enumerate()
+            .map(|(index, l)| {
+                parse_height_line(l).ok_or_else(|| ParseHeightmapError::InvalidLine {
+                    index,
+                    line: l.into(),
+                })
+            })
             .collect::<Result<Vec<Vec<u8>>, ParseHeightmapError>>()?;
 
         data.try_into()
@@ -282,12 +605,15 @@ impl TryFrom<Vec<Vec<u8>>> for Heightmap {
             return Err(err);
         }
 
-        Ok(Heightmap { data: value })
+        Ok(Heightmap {
+            data: value,
+            max_basin_height: DEFAULT_MAX_BASIN_HEIGHT,
+        })
     }
 }
 
 fn check_all_rows_have_same_len(data: &[Vec<u8>]) -> Option<ParseHeightmapError> {
-    let fst_row_len = data[0].len();
+    let fst_row_len = data.first()?.len();
     for (idx, row) in data.iter().skip(1).enumerate() {
         if row.len() != fst_row_len {
             return Some(ParseHeightmapError::UnexpectedLineLength {
@@ -310,36 +636,45 @@ fn sum_risk_levels(points: &[HeightPoint]) -> u32 {
     points.iter().map(|hp| (hp.height + 1) as u32).sum::<u32>()
 }
 
-/// CLI usage: cargo run -- input.txt
+/// CLI usage: cargo run -- input.txt [4|8]
+///
+/// The optional second argument is the connectivity of points, defaulting
+/// to 4.
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
-    let heightmap: Heightmap = io::BufReader::new(File::open(filename).expect("File not found"))
-        .lines()
-        .map(|l| {
-            let line = l.expect("Line not UTF-8");
-            parse_height_line(&line).unwrap_or_else(|| panic!("Invalid height line: {}", line))
-        })
-        .collect::<Vec<Vec<u8>>>()
-        .try_into()
-        .unwrap();
+    let connectivity = match env::args().nth(2).as_deref() {
+        None => Connectivity::default(),
+        Some("4") => Connectivity::Four,
+        Some("8") => Connectivity::Eight,
+        Some(c) => panic!("Invalid connectivity: {}", c),
+    };
 
-    let lps = heightmap.collect_low_points();
+    let heightmap = Heightmap::read(io::BufReader::new(
+        File::open(filename).expect("File not found"),
+    ))
+    .unwrap_or_else(|err| {
+        eprintln!("Invalid heightmap: {}", err);
+        process::exit(1);
+    });
 
-    println!("Sum of low point risk levels: {}", sum_risk_levels(&lps));
+    let lps = heightmap.collect_low_points(connectivity);
 
-    let bps_sizes = {
-        let mut sizes: Vec<usize> = lps
-            .iter()
-            .map(|p| heightmap.collect_basin(&p.point).len())
-            .collect();
-        sizes.sort_by(|a, b| b.cmp(a));
-        sizes
-    };
+    println!("Sum of low point risk levels: {}", sum_risk_levels(&lps));
 
     println!(
         "Product of 3 largest basin sizes: {}",
-        bps_sizes.iter().take(3).map(|s| *s as u32).product::<u32>()
+        heightmap.largest_basin_product_with(3, connectivity)
+    );
+
+    println!(
+        "Number of basins by watershed: {}",
+        heightmap
+            .watershed(connectivity)
+            .iter()
+            .map(|(_, id)| id + 1)
+            .max()
+            .unwrap_or(0)
     );
 }
 
@@ -355,7 +690,7 @@ mod tests {
             .try_into()
             .unwrap();
 
-        let lps = map.collect_low_points();
+        let lps = map.collect_low_points(Connectivity::Four);
 
         assert_eq!(lps, vec![HeightPoint::new(1, Point::new(1, 1))]);
     }
@@ -368,14 +703,14 @@ mod tests {
             .try_into()
             .unwrap();
 
-        let lps = map.collect_low_points();
+        let lps = map.collect_low_points(Connectivity::Four);
 
         assert_eq!(
             lps,
             vec![
                 HeightPoint::new(3, Point::new(0, 0)),
-                HeightPoint::new(3, Point::new(0, 1)),
                 HeightPoint::new(3, Point::new(1, 0)),
+                HeightPoint::new(3, Point::new(0, 1)),
                 HeightPoint::new(3, Point::new(1, 1)),
             ]
         );
@@ -390,14 +725,14 @@ mod tests {
             .try_into()
             .unwrap();
 
-        let lps = map.collect_low_points();
+        let lps = map.collect_low_points(Connectivity::Four);
 
         assert_eq!(
             lps,
             vec![
+                HeightPoint::new(1, Point::new(2, 0)),
                 HeightPoint::new(7, Point::new(0, 1)),
                 HeightPoint::new(7, Point::new(0, 3)),
-                HeightPoint::new(1, Point::new(2, 0)),
                 HeightPoint::new(8, Point::new(3, 3)),
             ]
         );
@@ -413,7 +748,7 @@ mod tests {
             .try_into()
             .unwrap();
 
-        let lps = map.collect_low_points();
+        let lps = map.collect_low_points(Connectivity::Four);
 
         assert_eq!(
             lps,
@@ -434,15 +769,15 @@ mod tests {
             .try_into()
             .unwrap();
 
-        let lps = map.collect_low_points();
+        let lps = map.collect_low_points(Connectivity::Four);
 
         assert_eq!(
             lps,
             vec![
                 HeightPoint::new(1, Point::new(1, 0)),
+                HeightPoint::new(0, Point::new(9, 0)),
                 HeightPoint::new(5, Point::new(2, 2)),
                 HeightPoint::new(5, Point::new(6, 4)),
-                HeightPoint::new(0, Point::new(9, 0)),
             ]
         );
     }
@@ -465,7 +800,7 @@ mod tests {
             .try_into()
             .unwrap();
 
-        let bps = map.collect_basin(&Point::new(1, 0));
+        let bps = map.collect_basin(&Point::new(1, 0), Connectivity::Four);
 
         assert_eq!(
             bps,
@@ -483,7 +818,7 @@ mod tests {
             .try_into()
             .unwrap();
 
-        let mut bps = map.collect_basin(&Point::new(2, 2));
+        let mut bps = map.collect_basin(&Point::new(2, 2), Connectivity::Four);
 
         bps.sort();
 
@@ -507,4 +842,276 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn collect_basin_when_8_connectivity() {
+        let map: Heightmap = "219\n\
+                              998\n\
+                              995\n"
+            .try_into()
+            .unwrap();
+
+        let mut bps = map.collect_basin(&Point::new(1, 0), Connectivity::Four);
+
+        bps.sort();
+
+        assert_eq!(bps, vec![Point::new(0, 0), Point::new(1, 0)]);
+
+        let mut bps = map.collect_basin(&Point::new(1, 0), Connectivity::Eight);
+
+        bps.sort();
+
+        assert_eq!(
+            bps,
+            vec![
+                Point::new(0, 0),
+                Point::new(1, 0),
+                Point::new(2, 1),
+                Point::new(2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn collect_low_points_when_8_connectivity() {
+        let map: Heightmap = "219\n\
+                              998\n\
+                              995\n"
+            .try_into()
+            .unwrap();
+
+        assert_eq!(
+            map.collect_low_points(Connectivity::Four),
+            vec![
+                HeightPoint::new(1, Point::new(1, 0)),
+                HeightPoint::new(5, Point::new(2, 2)),
+            ]
+        );
+        assert_eq!(
+            map.collect_low_points(Connectivity::Eight),
+            vec![
+                HeightPoint::new(1, Point::new(1, 0)),
+                HeightPoint::new(5, Point::new(2, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn label_basins_when_four() {
+        let map: Heightmap = "2199943210\n\
+                              3987894921\n\
+                              9856789892\n\
+                              8767896789\n\
+                              9899965678\n"
+            .try_into()
+            .unwrap();
+
+        let labels = map.label_basins(Connectivity::Four);
+
+        assert_eq!(labels[0][0], Some(0));
+        assert_eq!(labels[0][2], None);
+        assert_eq!(labels[0][9], Some(1));
+        assert_eq!(labels[2][2], Some(2));
+        assert_eq!(labels[4][6], Some(3));
+
+        let mut sizes = basin_sizes(&labels);
+
+        sizes.sort();
+
+        assert_eq!(sizes, vec![3, 9, 9, 14]);
+    }
+
+    #[test]
+    fn from_whitespace_separated_with_custom_max_basin_height() {
+        let map = Heightmap::from_whitespace_separated(
+            "12 10 30\n\
+             11 25 14\n\
+             20 30 13\n",
+        )
+        .unwrap()
+        .with_max_basin_height(20);
+
+        assert_eq!(map[&Point::new(1, 1)], 25);
+
+        assert_eq!(
+            map.collect_low_points(Connectivity::Four),
+            vec![
+                HeightPoint::new(10, Point::new(1, 0)),
+                HeightPoint::new(11, Point::new(0, 1)),
+                HeightPoint::new(13, Point::new(2, 2)),
+            ]
+        );
+
+        let mut bps = map.collect_basin(&Point::new(1, 0), Connectivity::Four);
+
+        bps.sort();
+
+        assert_eq!(
+            bps,
+            vec![Point::new(0, 0), Point::new(0, 1), Point::new(1, 0)]
+        );
+
+        assert_eq!(
+            basin_sizes(&map.label_basins(Connectivity::Four)),
+            vec![3, 2]
+        );
+    }
+
+    #[test]
+    fn from_whitespace_separated_when_ragged() {
+        assert!(matches!(
+            Heightmap::from_whitespace_separated("1 2 3\n4 5\n"),
+            Err(ParseHeightmapError::UnexpectedLineLength {
+                index: 1,
+                expected: 3,
+                actual: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn render_with_low_points_highlighted() {
+        let map: Heightmap = "2199943210\n\
+                              3987894921\n\
+                              9856789892\n\
+                              8767896789\n\
+                              9899965678\n"
+            .try_into()
+            .unwrap();
+
+        let lps: Vec<Point> = map
+            .collect_low_points(Connectivity::Four)
+            .into_iter()
+            .map(|hp| hp.point)
+            .collect();
+
+        assert_eq!(
+            map.render(&lps),
+            " 2 [1] 9  9  9  4  3  2  1 [0]\n\
+             \x203  9  8  7  8  9  4  9  2  1\n\
+             \x209  8 [5] 6  7  8  9  8  9  2\n\
+             \x208  7  6  7  8  9  6  7  8  9\n\
+             \x209  8  9  9  9  6 [5] 6  7  8\n"
+        );
+    }
+
+    #[test]
+    fn collect_low_points_sorted_by_row_then_col() {
+        let map: Heightmap = "52253\n\
+                              52259\n\
+                              99911\n"
+            .try_into()
+            .unwrap();
+
+        let points: Vec<Point> = map
+            .collect_low_points(Connectivity::Four)
+            .into_iter()
+            .map(|hp| hp.point)
+            .collect();
+
+        assert_eq!(
+            points,
+            vec![
+                Point::new(1, 0),
+                Point::new(2, 0),
+                Point::new(4, 0),
+                Point::new(1, 1),
+                Point::new(2, 1),
+                Point::new(3, 2),
+                Point::new(4, 2),
+            ]
+        );
+
+        assert_eq!(
+            map.low_point_heights(Connectivity::Four),
+            vec![2, 2, 3, 2, 2, 1, 1]
+        );
+    }
+
+    #[test]
+    fn watershed_partitions_all_points_below_max_basin_height() {
+        let map: Heightmap = "2199943210\n\
+                              3987894921\n\
+                              9856789892\n\
+                              8767896789\n\
+                              9899965678\n"
+            .try_into()
+            .unwrap();
+
+        let basin_points = map.watershed(Connectivity::Four);
+
+        let num_below_max = map.data.iter().flatten().filter(|&&h| h < 9).count();
+
+        assert_eq!(basin_points.len(), num_below_max);
+
+        let mut sizes = [0; 4];
+
+        for (_, id) in &basin_points {
+            sizes[*id as usize] += 1;
+        }
+
+        assert_eq!(sizes, [3, 9, 14, 9]);
+    }
+
+    #[test]
+    fn watershed_when_basins_touch() {
+        let map: Heightmap = "1234321\n\
+                              1234321\n"
+            .try_into()
+            .unwrap();
+
+        let ids: Vec<u32> = map
+            .watershed(Connectivity::Four)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+
+        assert_eq!(ids, vec![0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn read_when_ragged() {
+        assert!(matches!(
+            Heightmap::read("219\n398\n98\n".as_bytes()),
+            Err(ParseHeightmapError::UnexpectedLineLength {
+                index: 2,
+                expected: 3,
+                actual: 2
+            })
+        ));
+        assert!(matches!(
+            Heightmap::read("219\n3x8\n".as_bytes()),
+            Err(ParseHeightmapError::InvalidLine { index: 1, .. })
+        ));
+        assert_eq!(
+            Heightmap::read("219\n398\n".as_bytes()).unwrap().data,
+            vec![vec![2, 1, 9], vec![3, 9, 8]]
+        );
+    }
+
+    #[test]
+    fn largest_basins_when_tied_sizes() {
+        let map: Heightmap = "11911\n\
+                              99999\n\
+                              11191\n"
+            .try_into()
+            .unwrap();
+
+        let largest = map.largest_basins(3, Connectivity::Four);
+
+        assert_eq!(
+            largest,
+            vec![
+                (Point::new(0, 2), 3),
+                (Point::new(0, 0), 2),
+                (Point::new(3, 0), 2)
+            ]
+        );
+        assert_eq!(map.largest_basins(3, Connectivity::Four), largest);
+
+        assert_eq!(map.largest_basin_product(3), 12);
+        assert_eq!(map.largest_basin_product(2), 6);
+        assert_eq!(map.largest_basin_product(10), 12);
+        assert_eq!(map.largest_basin_product_with(3, Connectivity::Eight), 12);
+    }
 }
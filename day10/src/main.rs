@@ -3,12 +3,12 @@ use std::fs::File;
 use std::io::{self, BufRead};
 
 #[derive(Debug, PartialEq)]
-enum ChunksError {
+enum ChunksError<'a> {
     Illegal {
-        closing_chunk: &'static Chunk,
+        closing_chunk: &'a Chunk,
     },
     Incomplete {
-        missing_closing_chunks: Vec<&'static Chunk>,
+        missing_closing_chunks: Vec<&'a Chunk>,
     },
     Invalid {
         char: char,
@@ -23,130 +23,199 @@ struct Chunk {
     complete_close_char_score: u8,
 }
 
-const CHUNKS: [Chunk; 4] = [
-    Chunk {
-        open_char: '(',
-        close_char: ')',
-        illegal_close_char_score: 3,
-        complete_close_char_score: 1,
-    },
-    Chunk {
-        open_char: '[',
-        close_char: ']',
-        illegal_close_char_score: 57,
-        complete_close_char_score: 2,
-    },
-    Chunk {
-        open_char: '{',
-        close_char: '}',
-        illegal_close_char_score: 1197,
-        complete_close_char_score: 3,
-    },
-    Chunk {
-        open_char: '<',
-        close_char: '>',
-        illegal_close_char_score: 25137,
-        complete_close_char_score: 4,
-    },
+/// Open char, close char, illegal close char score, and complete close
+/// char score of each chunk
+const DEFAULT_CHUNKS: [(char, char, u16, u8); 4] = [
+    ('(', ')', 3, 1),
+    ('[', ']', 57, 2),
+    ('{', '}', 1197, 3),
+    ('<', '>', 25137, 4),
 ];
 
-impl Chunk {
-    fn get(c: char) -> Option<&'static Chunk> {
-        match c {
-            '(' | ')' => Some(&CHUNKS[0]),
-            '[' | ']' => Some(&CHUNKS[1]),
-            '{' | '}' => Some(&CHUNKS[2]),
-            '<' | '>' => Some(&CHUNKS[3]),
-            _ => None,
+#[derive(Debug)]
+struct ChunkSet {
+    chunks: Vec<Chunk>,
+}
+
+impl ChunkSet {
+    fn new(chunks: &[(char, char, u16, u8)]) -> ChunkSet {
+        ChunkSet {
+            chunks: chunks
+                .iter()
+                .map(
+                    |&(
+                        open_char,
+                        close_char,
+                        illegal_close_char_score,
+                        complete_close_char_score,
+                    )| {
+                        Chunk {
+                            open_char,
+                            close_char,
+                            illegal_close_char_score,
+                            complete_close_char_score,
+                        }
+                    },
+                )
+                .collect(),
         }
     }
+
+    /// Finds the chunk having `c` as its open or close char.
+    fn get(&self, c: char) -> Option<&Chunk> {
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.open_char == c || chunk.close_char == c)
+    }
+}
+
+impl Default for ChunkSet {
+    fn default() -> Self {
+        ChunkSet::new(&DEFAULT_CHUNKS)
+    }
+}
+
+/// The result of checking the chunks of a line
+#[derive(Debug, PartialEq)]
+enum LineClass<'a> {
+    Valid,
+    /// The first illegal closing chunk
+    Corrupted(&'a Chunk),
+    /// The missing closing chunks, in closing order
+    Incomplete(Vec<&'a Chunk>),
+    /// A char not belonging to any chunk
+    Invalid(char),
 }
 
-fn check_chunks_error(str: &str) -> Option<ChunksError> {
-    use ChunksError::*;
+fn classify<'a>(chunk_set: &'a ChunkSet, line: &str) -> LineClass<'a> {
+    use LineClass::*;
 
     let mut stack: Vec<&Chunk> = vec![];
 
-    for c in str.chars() {
-        if let Some(chunk) = Chunk::get(c) {
+    for c in line.chars() {
+        if let Some(chunk) = chunk_set.get(c) {
             if chunk.open_char == c {
                 stack.push(chunk);
             } else if let Some(expected_chunk) = stack.pop() {
                 if expected_chunk != chunk {
-                    return Some(Illegal {
-                        closing_chunk: chunk,
-                    });
+                    return Corrupted(chunk);
                 }
             } else {
-                return Some(Illegal {
-                    closing_chunk: chunk,
-                });
+                return Corrupted(chunk);
             }
         } else {
-            return Some(Invalid { char: c });
+            return Invalid(c);
         }
     }
 
     if stack.is_empty() {
-        None
+        Valid
     } else {
         stack.reverse();
-        Some(Incomplete {
-            missing_closing_chunks: stack,
-        })
+        Incomplete(stack)
     }
 }
 
-fn middle_score_of_missing_closing_chunkses(chunkses: Vec<Vec<&Chunk>>) -> Option<u64> {
-    fn line_score(chunks: &[&Chunk]) -> u64 {
-        chunks.iter().fold(0u64, |sum, c| {
-            sum * 5 + (c.complete_close_char_score as u64)
-        })
+fn check_chunks_error<'a>(chunk_set: &'a ChunkSet, str: &str) -> Option<ChunksError<'a>> {
+    match classify(chunk_set, str) {
+        LineClass::Valid => None,
+        LineClass::Corrupted(closing_chunk) => Some(ChunksError::Illegal { closing_chunk }),
+        LineClass::Incomplete(missing_closing_chunks) => Some(ChunksError::Incomplete {
+            missing_closing_chunks,
+        }),
+        LineClass::Invalid(char) => Some(ChunksError::Invalid { char }),
     }
+}
 
-    if chunkses.is_empty() {
-        return None;
-    }
+/// Returns the 0-based index and error of each line having an error.
+fn analyze_lines<'a>(
+    chunk_set: &'a ChunkSet,
+    reader: impl BufRead,
+) -> Vec<(usize, ChunksError<'a>)> {
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            check_chunks_error(chunk_set, &line.expect("Line not UTF-8")).map(|err| (idx, err))
+        })
+        .collect()
+}
 
-    let mut scores: Vec<u64> = chunkses.iter().map(|cs| line_score(cs)).collect();
-    scores.sort();
-    Some(scores[scores.len() / 2])
+fn completion_string(missing: &[&Chunk]) -> String {
+    missing.iter().map(|c| c.close_char).collect()
 }
 
-/// CLI usage: cargo run -- input.txt
-fn main() {
-    let filename = env::args().nth(1).expect("Missing input file");
+fn completion_score(missing: &[&Chunk]) -> u64 {
+    missing.iter().fold(0u64, |sum, c| {
+        sum * 5 + (c.complete_close_char_score as u64)
+    })
+}
 
-    let (illegal_closing_chunks, missing_closing_chunkses): (Vec<&Chunk>, Vec<Vec<&Chunk>>) = {
-        let mut illegal_closing_chunks: Vec<&Chunk> = vec![];
-        let mut missing_closing_chunkses: Vec<Vec<&Chunk>> = vec![];
+#[derive(Debug, PartialEq)]
+struct ScoreResult {
+    /// The sum of the scores of illegal closing chars
+    syntax_error_score: u64,
+    /// The middle score of completing incomplete lines
+    completion_score: Option<u64>,
+    /// The completion string having the middle score
+    middle_completion: Option<String>,
+}
 
-        for line in io::BufReader::new(File::open(filename).expect("File not found")).lines() {
-            match check_chunks_error(&line.expect("Line not UTF-8")) {
-                Some(ChunksError::Illegal { closing_chunk }) => {
-                    illegal_closing_chunks.push(closing_chunk)
-                }
-                Some(ChunksError::Incomplete {
-                    missing_closing_chunks,
-                }) => missing_closing_chunkses.push(missing_closing_chunks),
-                _ => (),
+/// Scores illegal and incomplete lines in one pass. Lines with invalid
+/// chars are skipped.
+fn score_lines(chunk_set: &ChunkSet, reader: impl BufRead) -> ScoreResult {
+    let mut syntax_error_score: u64 = 0;
+    let mut completions: Vec<(u64, String)> = vec![];
+
+    for (_, err) in analyze_lines(chunk_set, reader) {
+        match err {
+            ChunksError::Illegal { closing_chunk } => {
+                syntax_error_score += closing_chunk.illegal_close_char_score as u64
             }
+            ChunksError::Incomplete {
+                missing_closing_chunks,
+            } => completions.push((
+                completion_score(&missing_closing_chunks),
+                completion_string(&missing_closing_chunks),
+            )),
+            ChunksError::Invalid { .. } => (),
         }
+    }
+
+    completions.sort();
 
-        (illegal_closing_chunks, missing_closing_chunkses)
+    let middle_idx = completions.len() / 2;
+
+    let (completion_score, middle_completion) = match completions.into_iter().nth(middle_idx) {
+        Some((score, completion)) => (Some(score), Some(completion)),
+        None => (None, None),
     };
 
+    ScoreResult {
+        syntax_error_score,
+        completion_score,
+        middle_completion,
+    }
+}
+
+/// CLI usage: cargo run -- input.txt
+fn main() {
+    let filename = env::args().nth(1).expect("Missing input file");
+
+    let result = score_lines(
+        &ChunkSet::default(),
+        io::BufReader::new(File::open(filename).expect("File not found")),
+    );
+
     println!(
         "Sum of illegal closing chars: {}",
-        illegal_closing_chunks
-            .iter()
-            .map(|c| c.illegal_close_char_score as u32)
-            .sum::<u32>()
+        result.syntax_error_score
     );
 
     println!(
-        "Middle score of completing missing closing chars: {}",
-        middle_score_of_missing_closing_chunkses(missing_closing_chunkses).unwrap()
+        "Middle score of completing missing closing chars: {} ({})",
+        result.completion_score.expect("No incomplete lines"),
+        result.middle_completion.unwrap()
     );
 }
 
@@ -156,25 +225,26 @@ mod tests {
 
     #[test]
     fn check_chunks_error_when_none() {
-        assert!(check_chunks_error("(()[{<>}][])").is_none());
+        assert!(check_chunks_error(&ChunkSet::default(), "(()[{<>}][])").is_none());
     }
 
     #[test]
     fn check_chunks_error_invalid() {
         assert_eq!(
-            check_chunks_error("a"),
+            check_chunks_error(&ChunkSet::default(), "a"),
             Some(ChunksError::Invalid { char: 'a' })
         );
     }
 
     #[test]
     fn check_chunks_error_incomplete() {
+        let chunk_set = ChunkSet::default();
         assert_eq!(
-            check_chunks_error("([][<"),
+            check_chunks_error(&chunk_set, "([][<"),
             Some(ChunksError::Incomplete {
                 missing_closing_chunks: vec!['>', ']', ')']
                     .into_iter()
-                    .map(|c| Chunk::get(c).unwrap())
+                    .map(|c| chunk_set.get(c).unwrap())
                     .collect()
             })
         );
@@ -182,11 +252,124 @@ mod tests {
 
     #[test]
     fn check_chunks_error_illegal() {
+        let chunk_set = ChunkSet::default();
+        assert_eq!(
+            check_chunks_error(&chunk_set, "([<])"),
+            Some(ChunksError::Illegal {
+                closing_chunk: chunk_set.get(']').unwrap()
+            })
+        );
+    }
+
+    #[test]
+    fn classify_each_class() {
+        let chunk_set = ChunkSet::default();
+        assert_eq!(classify(&chunk_set, "(()[{<>}][])"), LineClass::Valid);
+        assert_eq!(classify(&chunk_set, ""), LineClass::Valid);
         assert_eq!(
-            check_chunks_error("([<])"),
+            classify(&chunk_set, "{([(<{}[<>[]}>{[]{[(<()>"),
+            LineClass::Corrupted(chunk_set.get('}').unwrap())
+        );
+        assert_eq!(
+            classify(&chunk_set, ")"),
+            LineClass::Corrupted(chunk_set.get(')').unwrap())
+        );
+        assert_eq!(
+            classify(&chunk_set, "([][<"),
+            LineClass::Incomplete(
+                vec!['>', ']', ')']
+                    .into_iter()
+                    .map(|c| chunk_set.get(c).unwrap())
+                    .collect()
+            )
+        );
+        assert_eq!(classify(&chunk_set, "(a)"), LineClass::Invalid('a'));
+    }
+
+    #[test]
+    fn completion_string_of_incomplete() {
+        match check_chunks_error(&ChunkSet::default(), "[({(<(())[]>[[{[]{<()<>>") {
+            Some(ChunksError::Incomplete {
+                missing_closing_chunks,
+            }) => {
+                assert_eq!(completion_string(&missing_closing_chunks), "}}]])})]");
+                assert_eq!(completion_score(&missing_closing_chunks), 288957);
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn check_chunks_error_with_custom_chunk_set() {
+        let chunk_set = ChunkSet::new(&[('«', '»', 100, 5), ('(', ')', 3, 1)]);
+        assert_eq!(
+            check_chunks_error(&chunk_set, "«(»"),
             Some(ChunksError::Illegal {
-                closing_chunk: Chunk::get(']').unwrap()
+                closing_chunk: chunk_set.get('»').unwrap()
             })
         );
+        match check_chunks_error(&chunk_set, "(««()»") {
+            Some(ChunksError::Incomplete {
+                missing_closing_chunks,
+            }) => {
+                assert_eq!(completion_string(&missing_closing_chunks), "»)");
+                assert_eq!(completion_score(&missing_closing_chunks), 26);
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(
+            check_chunks_error(&chunk_set, "[]"),
+            Some(ChunksError::Invalid { char: '[' })
+        );
+    }
+
+    #[test]
+    fn analyze_lines_with_errors() {
+        let chunk_set = ChunkSet::default();
+        let input = "(()[{<>}][])\n\
+                     {([(<{}[<>[]}>{[]{[(<()>\n\
+                     [({(<(())[]>[[{[]{<()<>>\n";
+        let res = analyze_lines(&chunk_set, input.as_bytes());
+        assert_eq!(res.len(), 2);
+        assert_eq!(
+            res[0],
+            (
+                1,
+                ChunksError::Illegal {
+                    closing_chunk: chunk_set.get('}').unwrap()
+                }
+            )
+        );
+        assert!(matches!(res[1], (2, ChunksError::Incomplete { .. })));
+    }
+
+    #[test]
+    fn score_lines_with_example() {
+        let input = "[({(<(())[]>[[{[]{<()<>>\n\
+                     [(()[<>])]({[<{<<[]>>(\n\
+                     {([(<{}[<>[]}>{[]{[(<()>\n\
+                     (((({<>}<{<{<>}{[]{[]{}\n\
+                     [[<[([]))<([[{}[[()]]]\n\
+                     [{[{({}]{}}([{[{{{}}([]\n\
+                     {<[[]]>}<{[{[{[]{()[[[]\n\
+                     [<(<(<(<{}))><([]([]()\n\
+                     <{([([[(<>()){}]>(<<{{\n\
+                     <{([{{}}[<[[[<>{}]]]>[]]\n";
+        let res = score_lines(&ChunkSet::default(), input.as_bytes());
+        assert_eq!(res.syntax_error_score, 26397);
+        assert_eq!(res.completion_score, Some(288957));
+        assert_eq!(res.middle_completion.as_deref(), Some("}}]])})]"));
+    }
+
+    #[test]
+    fn score_lines_when_no_errors() {
+        assert_eq!(
+            score_lines(&ChunkSet::default(), "()\n".as_bytes()),
+            ScoreResult {
+                syntax_error_score: 0,
+                completion_score: None,
+                middle_completion: None,
+            }
+        );
     }
 }
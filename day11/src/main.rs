@@ -1,21 +1,41 @@
 use bitvec::prelude as bv;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::iter;
 use std::ops::{Index, IndexMut};
 use std::str::FromStr;
 
 #[derive(Debug)]
 enum ParseOctopusMapError {
     EnergyLevel(char),
-    UnexpectedNumCols(usize),
-    UnexpectedNumRows(usize),
+    UnexpectedNumCols {
+        row: usize,
+        expected: usize,
+        actual: usize,
+    },
+    Empty,
 }
 
-const OCTOPUS_MAP_COLS: usize = 10;
-const OCTOPUS_MAP_ROWS: usize = 10;
-
-type OctopusLines = [[u8; OCTOPUS_MAP_COLS]; OCTOPUS_MAP_ROWS];
+impl fmt::Display for ParseOctopusMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseOctopusMapError::*;
+        match *self {
+            EnergyLevel(c) => write!(f, "Invalid energy level: {}", c),
+            UnexpectedNumCols {
+                row,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Unexpected number of columns at row {} (should be {}, but was {})",
+                row, expected, actual
+            ),
+            Empty => write!(f, "Empty octopus map"),
+        }
+    }
+}
 
 const OCTOPUS_MIN_FLASH_ENERGY_LEVEL: u8 = 10;
 
@@ -31,29 +51,20 @@ const OCTOPUS_NEIGHBOUR_DELTAS: [(isize, isize); 8] = [
 ];
 
 #[derive(Debug)]
-struct OctopusLine([u8; OCTOPUS_MAP_COLS]);
+struct OctopusLine(Vec<u8>);
 
 impl FromStr for OctopusLine {
     type Err = ParseOctopusMapError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut res = [0; OCTOPUS_MAP_COLS];
-        let mut num_cols = 0;
-
-        for (idx, c) in s.chars().take(OCTOPUS_MAP_COLS).enumerate() {
-            if let Some(n) = c.to_digit(10) {
-                res[idx] = n as u8;
-            } else {
-                return Err(ParseOctopusMapError::EnergyLevel(c));
-            }
-            num_cols += 1;
-        }
-
-        if num_cols != OCTOPUS_MAP_COLS {
-            return Err(ParseOctopusMapError::UnexpectedNumCols(num_cols));
-        }
-
-        Ok(OctopusLine(res))
+        s.chars()
+            .map(|c| {
+                c.to_digit(10)
+                    .map(|n| n as u8)
+                    .ok_or(ParseOctopusMapError::EnergyLevel(c))
+            })
+            .collect::<Result<Vec<u8>, ParseOctopusMapError>>()
+            .map(OctopusLine)
     }
 }
 
@@ -61,12 +72,12 @@ impl FromStr for OctopusLine {
 struct XY(usize, usize);
 
 impl XY {
-    fn index1d(&self) -> usize {
+    fn index1d(&self, cols: usize) -> usize {
         let XY(x, y) = *self;
-        y * OCTOPUS_MAP_ROWS + x
+        y * cols + x
     }
 
-    fn neighbours(&self) -> Vec<XY> {
+    fn neighbours(&self, cols: usize, rows: usize) -> Vec<XY> {
         OCTOPUS_NEIGHBOUR_DELTAS
             .iter()
             .filter_map(|(dx, dy)| {
@@ -75,7 +86,7 @@ impl XY {
                 let my = y.checked_add_signed(*dy);
                 match (mx, my) {
                     (Some(x), Some(y)) => {
-                        if y < OCTOPUS_MAP_ROWS && x < OCTOPUS_MAP_COLS {
+                        if y < rows && x < cols {
                             Some(XY(x, y))
                         } else {
                             None
@@ -88,24 +99,36 @@ impl XY {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct OctopusMap {
-    rows: OctopusLines,
+    data: Vec<Vec<u8>>,
+    cols: usize,
+    rows: usize,
 }
 
 impl OctopusMap {
-    fn new(rows: OctopusLines) -> OctopusMap {
-        OctopusMap { rows }
+    /// Expects all rows to have the same number of columns.
+    fn new(data: Vec<Vec<u8>>) -> OctopusMap {
+        let rows = data.len();
+        let cols = data.first().map_or(0, |r| r.len());
+        OctopusMap { data, cols, rows }
     }
 
     /// Run one step of energy simulation, returning the number of
     /// flashes happened during the step.
-    fn step_energy_simulation(&mut self) -> u8 {
-        let mut num_flashes = 0u8;
-        let mut have_flashed = bv::bitvec![0; OCTOPUS_MAP_COLS * OCTOPUS_MAP_ROWS];
+    fn step_energy_simulation(&mut self) -> usize {
+        let mut have_flashed = bv::bitvec![0; self.cols * self.rows];
+        self.step_energy_simulation_tracked(&mut have_flashed)
+    }
+
+    /// Like `step_energy_simulation`, but marks the octopuses that flashed
+    /// in `have_flashed`, indexed by `XY::index1d`. Expects `have_flashed`
+    /// to be all zeros.
+    fn step_energy_simulation_tracked(&mut self, have_flashed: &mut bv::BitVec) -> usize {
+        let mut num_flashes = 0;
         let mut about_to_flash: Vec<XY> = vec![];
 
-        for (y, row) in self.rows.iter_mut().enumerate() {
+        for (y, row) in self.data.iter_mut().enumerate() {
             for (x, energy_level) in row.iter_mut().enumerate() {
                 *energy_level += 1;
                 if *energy_level >= OCTOPUS_MIN_FLASH_ENERGY_LEVEL {
@@ -115,7 +138,7 @@ impl OctopusMap {
         }
 
         while let Some(xy) = about_to_flash.pop() {
-            if have_flashed[xy.index1d()] {
+            if have_flashed[xy.index1d(self.cols)] {
                 continue;
             }
 
@@ -123,10 +146,10 @@ impl OctopusMap {
 
             num_flashes += 1;
 
-            have_flashed.set(xy.index1d(), true);
+            have_flashed.set(xy.index1d(self.cols), true);
 
-            for n_xy in xy.neighbours() {
-                if !have_flashed[n_xy.index1d()] {
+            for n_xy in xy.neighbours(self.cols, self.rows) {
+                if !have_flashed[n_xy.index1d(self.cols)] {
                     let energy_level = &mut self[&n_xy];
                     *energy_level += 1;
                     if *energy_level >= OCTOPUS_MIN_FLASH_ENERGY_LEVEL {
@@ -138,6 +161,46 @@ impl OctopusMap {
 
         num_flashes
     }
+
+    /// Run `steps` steps of energy simulation, returning how many times
+    /// each octopus flashed, indexed by row and column.
+    fn simulate_with_flash_counts(&mut self, steps: usize) -> Vec<Vec<u32>> {
+        let mut flash_counts = vec![vec![0u32; self.cols]; self.rows];
+
+        for _ in 0..steps {
+            let mut have_flashed = bv::bitvec![0; self.cols * self.rows];
+
+            self.step_energy_simulation_tracked(&mut have_flashed);
+
+            for idx in have_flashed.iter_ones() {
+                flash_counts[idx / self.cols][idx % self.cols] += 1;
+            }
+        }
+
+        flash_counts
+    }
+
+    /// Iterate simulation steps endlessly, mutating the map in place and
+    /// yielding the number of flashes happened during each step.
+    fn steps(&mut self) -> impl Iterator<Item = usize> + '_ {
+        iter::repeat_with(move || self.step_energy_simulation())
+    }
+
+    /// Run the simulation until a step where all octopuses flash,
+    /// returning the 1-based number of that step. Returns 0 for an empty
+    /// map.
+    fn steps_until_all_flash(&mut self) -> usize {
+        let num_octopuses = self.cols * self.rows;
+
+        if num_octopuses == 0 {
+            return 0;
+        }
+
+        self.steps()
+            .position(|num_flashes| num_flashes == num_octopuses)
+            .unwrap()
+            + 1
+    }
 }
 
 impl Index<&XY> for OctopusMap {
@@ -145,14 +208,30 @@ impl Index<&XY> for OctopusMap {
 
     fn index(&self, index: &XY) -> &Self::Output {
         let XY(x, y) = *index;
-        &self.rows[y][x]
+        &self.data[y][x]
     }
 }
 
 impl IndexMut<&XY> for OctopusMap {
     fn index_mut(&mut self, index: &XY) -> &mut Self::Output {
         let XY(x, y) = *index;
-        &mut self.rows[y][x]
+        &mut self.data[y][x]
+    }
+}
+
+/// Renders the energy levels in the same format as parsed, one row per
+/// line.
+impl fmt::Display for OctopusMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (y, row) in self.data.iter().enumerate() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            for energy_level in row {
+                write!(f, "{}", energy_level)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -162,7 +241,6 @@ impl FromStr for OctopusMap {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let lines = s
             .lines()
-            .take(OCTOPUS_MAP_ROWS)
             .map(|r| r.parse())
             .collect::<Result<Vec<OctopusLine>, ParseOctopusMapError>>()?;
 
@@ -170,64 +248,70 @@ impl FromStr for OctopusMap {
     }
 }
 
+/// Takes the number of columns from the first line, requiring the rest of
+/// the lines to have the same number.
 impl TryFrom<Vec<OctopusLine>> for OctopusMap {
     type Error = ParseOctopusMapError;
 
     fn try_from(value: Vec<OctopusLine>) -> Result<Self, Self::Error> {
-        let data: OctopusLines = value
-            .into_iter()
-            .map(|l| l.0)
-            .collect::<Vec<_>>()
-            .try_into()
-            .map_err(|v: Vec<[u8; OCTOPUS_MAP_COLS]>| {
-                ParseOctopusMapError::UnexpectedNumRows(v.len())
-            })?;
-
-        Ok(OctopusMap::new(data))
+        let cols = value.first().ok_or(ParseOctopusMapError::Empty)?.0.len();
+
+        for (idx, line) in value.iter().enumerate().skip(1) {
+            if line.0.len() != cols {
+                return Err(ParseOctopusMapError::UnexpectedNumCols {
+                    row: idx,
+                    expected: cols,
+                    actual: line.0.len(),
+                });
+            }
+        }
+
+        Ok(OctopusMap::new(value.into_iter().map(|l| l.0).collect()))
     }
 }
 
+/// Parses the octopus map from `input` and returns the total number of
+/// flashes after `steps` steps.
+#[cfg(test)]
+fn simulate_n(input: &str, steps: usize) -> Result<usize, ParseOctopusMapError> {
+    let mut map: OctopusMap = input.parse()?;
+    Ok(map.steps().take(steps).sum())
+}
+
 /// CLI usage: cargo run -- input.txt
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
     let mut map: OctopusMap = io::BufReader::new(File::open(filename).expect("File not found"))
         .lines()
-        .take(OCTOPUS_MAP_ROWS)
         .map(|l| {
             let line = &l.expect("Line not UTF-8");
             line.parse()
-                .unwrap_or_else(|e| panic!("Invalid octopus map line ({:?}): {}", e, line))
+                .unwrap_or_else(|e| panic!("Invalid octopus map line ({}): {}", e, line))
         })
         .collect::<Vec<OctopusLine>>()
         .try_into()
-        .unwrap();
+        .unwrap_or_else(|e| panic!("Invalid octopus map: {}", e));
 
-    let mut sum_flashes_after_100_steps: usize = 0;
-    let mut num_steps: usize = 0;
-    let mut all_octopuses_flash_at_step: Option<usize> = None;
+    let sum_flashes_after_100_steps: usize = map.clone().steps().take(100).sum();
 
-    while all_octopuses_flash_at_step.is_none() || num_steps < 100 {
-        num_steps += 1;
+    let max_flashes_of_octopus_in_100_steps = map
+        .clone()
+        .simulate_with_flash_counts(100)
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(0);
 
-        let num_flashes = map.step_energy_simulation() as usize;
-
-        if all_octopuses_flash_at_step.is_none()
-            && num_flashes == OCTOPUS_MAP_COLS * OCTOPUS_MAP_ROWS
-        {
-            all_octopuses_flash_at_step = Some(num_steps);
-        }
-
-        if num_steps < 100 {
-            sum_flashes_after_100_steps += num_flashes;
-        }
-    }
+    let all_octopuses_flash_at_step = map.steps_until_all_flash();
 
     println!(
         "Sum flashes after 100 steps: {}\n\
+         Most flashes of an octopus after 100 steps: {}\n\
          All octopuses flash at step {}",
         sum_flashes_after_100_steps,
-        all_octopuses_flash_at_step.unwrap()
+        max_flashes_of_octopus_in_100_steps,
+        all_octopuses_flash_at_step
     );
 }
 
@@ -235,6 +319,17 @@ fn main() {
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = "5483143223\n\
+                           2745854711\n\
+                           5264556173\n\
+                           6141336146\n\
+                           6357385478\n\
+                           4167524645\n\
+                           2176841721\n\
+                           6882881134\n\
+                           4846848554\n\
+                           5283751526";
+
     #[test]
     fn step_simulation() {
         let mut map: OctopusMap = "5483143223\n\
@@ -288,4 +383,108 @@ mod tests {
 
         assert_eq!(map, expected_map);
     }
+
+    #[test]
+    fn step_simulation_5x5() {
+        let mut map: OctopusMap = "11111\n\
+                                   19991\n\
+                                   19191\n\
+                                   19991\n\
+                                   11111"
+            .parse()
+            .unwrap();
+
+        assert_eq!((map.cols, map.rows), (5, 5));
+
+        assert_eq!(map.step_energy_simulation(), 9);
+
+        let mut expected_map: OctopusMap = "34543\n\
+                                            40004\n\
+                                            50005\n\
+                                            40004\n\
+                                            34543"
+            .parse()
+            .unwrap();
+
+        assert_eq!(map, expected_map);
+
+        assert_eq!(map.step_energy_simulation(), 0);
+
+        expected_map = "45654\n\
+                        51115\n\
+                        61116\n\
+                        51115\n\
+                        45654"
+            .parse()
+            .unwrap();
+
+        assert_eq!(map, expected_map);
+    }
+
+    #[test]
+    fn parse_map_with_inconsistent_cols() {
+        assert!(matches!(
+            "123\n45\n".parse::<OctopusMap>(),
+            Err(ParseOctopusMapError::UnexpectedNumCols {
+                row: 1,
+                expected: 3,
+                actual: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn steps_iterator() {
+        let mut map: OctopusMap = EXAMPLE.parse().unwrap();
+        assert_eq!(map.steps().take(2).collect::<Vec<_>>(), vec![0, 35]);
+        assert_eq!(map.steps().take(8).sum::<usize>(), 204 - 35);
+    }
+
+    #[test]
+    fn steps_iterator_sum_after_100_steps() {
+        let mut map: OctopusMap = EXAMPLE.parse().unwrap();
+        assert_eq!(map.steps().take(100).sum::<usize>(), 1656);
+    }
+
+    #[test]
+    fn steps_until_all_flash() {
+        let mut map: OctopusMap = EXAMPLE.parse().unwrap();
+        assert_eq!(map.steps_until_all_flash(), 195);
+    }
+
+    #[test]
+    fn steps_until_all_flash_when_empty() {
+        assert_eq!(OctopusMap::new(vec![]).steps_until_all_flash(), 0);
+    }
+
+    #[test]
+    fn simulate_with_flash_counts() {
+        let mut map: OctopusMap = EXAMPLE.parse().unwrap();
+        let flash_counts = map.simulate_with_flash_counts(10);
+        assert_eq!(flash_counts[0][0], 2);
+        assert_eq!(flash_counts[0][9], 1);
+        assert_eq!(flash_counts[9][0], 2);
+        assert_eq!(flash_counts[9][9], 2);
+        assert_eq!(flash_counts.iter().flatten().sum::<u32>(), 204);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let mut map: OctopusMap = EXAMPLE.parse().unwrap();
+        assert_eq!(map.to_string(), EXAMPLE);
+
+        map.steps().take(2).last();
+        assert_eq!(map.to_string().parse::<OctopusMap>().unwrap(), map);
+        assert!(map.to_string().starts_with("8807476555\n5089087054\n"));
+    }
+
+    #[test]
+    fn simulate_n() {
+        assert_eq!(super::simulate_n(EXAMPLE, 10).unwrap(), 204);
+        assert_eq!(super::simulate_n(EXAMPLE, 100).unwrap(), 1656);
+        assert!(matches!(
+            super::simulate_n("12\n3", 1),
+            Err(ParseOctopusMapError::UnexpectedNumCols { .. })
+        ));
+    }
 }
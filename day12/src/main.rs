@@ -1,9 +1,11 @@
 use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
-use std::ptr;
 use std::rc::Rc;
 use std::str::FromStr;
 
@@ -14,6 +16,35 @@ enum ParseCaveGraphError {
     MissingEndNode,
 }
 
+impl fmt::Display for ParseCaveGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseCaveGraphError::*;
+        match *self {
+            UnexpectedNumNodesInLink(n) => {
+                write!(f, "Unexpected number of nodes in link: {}", n)
+            }
+            MissingStartNode => write!(f, "Missing start node"),
+            MissingEndNode => write!(f, "Missing end node"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum TwiceCaveError {
+    UnknownCave(String),
+    NotSmallCave(String),
+}
+
+impl fmt::Display for TwiceCaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TwiceCaveError::*;
+        match *self {
+            UnknownCave(ref cave) => write!(f, "Unknown cave: {}", cave),
+            NotSmallCave(ref cave) => write!(f, "Not a small cave: {}", cave),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CaveLink {
     node_a: String,
@@ -41,10 +72,10 @@ impl FromStr for CaveLink {
 
 #[derive(Debug, PartialEq)]
 enum NodeKind {
-    StartCave,
-    EndCave,
-    BigCave,
-    SmallCave,
+    Start,
+    End,
+    Big,
+    Small,
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -53,13 +84,13 @@ struct Node(String);
 impl Node {
     fn kind(&self) -> NodeKind {
         if self.0 == "start" {
-            NodeKind::StartCave
+            NodeKind::Start
         } else if self.0 == "end" {
-            NodeKind::EndCave
+            NodeKind::End
         } else if self.0.chars().next().unwrap().is_uppercase() {
-            NodeKind::BigCave
+            NodeKind::Big
         } else {
-            NodeKind::SmallCave
+            NodeKind::Small
         }
     }
 }
@@ -72,79 +103,223 @@ struct CaveGraph {
 }
 
 impl CaveGraph {
-    fn paths_with_small_caves_once(&self) -> HashSet<Vec<Node>> {
-        self.paths(|path, n| !path.contains(&n))
+    /// The number of neighbours of a node, or `None` if there's no such
+    /// node.
+    #[cfg(test)]
+    fn degree(&self, node: &str) -> Option<usize> {
+        self.graph.get(&Node(node.to_string())).map(|ns| ns.len())
     }
 
-    fn paths_with_one_small_cave_twice(&self) -> HashSet<Vec<Node>> {
-        self.paths(|path, n| {
-            let mut node_occurences: HashMap<&Node, usize> = HashMap::new();
-            for n in path.iter().filter(|n| n.kind() == NodeKind::SmallCave) {
-                let num = node_occurences.entry(n).or_insert(0);
-                *num += 1;
-            }
-            let n_occ = *node_occurences.get(n).unwrap_or(&0);
-            if n_occ == 0 {
+    /// Whether `node` can be reached from the start cave, ignoring the
+    /// rules of visiting small caves.
+    #[cfg(test)]
+    fn is_reachable(&self, node: &str) -> bool {
+        let target = Node(node.to_string());
+
+        let mut visited: HashSet<&Node> = HashSet::from([Rc::as_ref(&self.start)]);
+        let mut visit_next: VecDeque<&Node> = VecDeque::from([Rc::as_ref(&self.start)]);
+
+        while let Some(curr_node) = visit_next.pop_front() {
+            if *curr_node == target {
                 return true;
             }
-            n_occ == 1 && node_occurences.values().all(|&num| num < 2)
-        })
+
+            for neighbour in self.graph.get(curr_node).unwrap() {
+                if visited.insert(neighbour) {
+                    visit_next.push_back(neighbour);
+                }
+            }
+        }
+
+        false
     }
 
-    fn paths<F>(&self, include_small_cave: F) -> HashSet<Vec<Node>>
-    where
-        F: Fn(&Vec<&Node>, &Node) -> bool,
-    {
-        let mut result: Vec<Vec<&Node>> = vec![];
+    fn count_paths_small_once(&self) -> usize {
+        self.count_paths(false)
+    }
 
-        let mut visit_paths_next: Vec<Vec<&Node>> = vec![vec![&self.start]];
+    fn count_paths_one_twice(&self) -> usize {
+        self.count_paths(true)
+    }
 
-        while let Some(curr_path) = visit_paths_next.pop() {
-            let curr_node = *curr_path.last().unwrap();
+    fn count_paths(&self, allow_one_twice: bool) -> usize {
+        let mut small_cave_visits: HashMap<&Node, usize> = HashMap::new();
+        self.count_paths_from(&self.start, &mut small_cave_visits, allow_one_twice)
+    }
 
-            if curr_node == Rc::as_ref(&self.end) {
-                result.push(curr_path);
+    /// Depth-first search, keeping track of how many times each small cave
+    /// is on the current path.
+    fn count_paths_from<'a>(
+        &'a self,
+        node: &'a Node,
+        small_cave_visits: &mut HashMap<&'a Node, usize>,
+        allow_one_twice: bool,
+    ) -> usize {
+        if node == Rc::as_ref(&self.end) {
+            return 1;
+        }
+
+        let mut num_paths = 0;
+
+        for neighbour in self
+            .graph
+            .get(node)
+            .unwrap()
+            .iter()
+            .filter(|n| n.kind() != NodeKind::Start)
+        {
+            let neighbour = Rc::as_ref(neighbour);
+
+            if neighbour.kind() != NodeKind::Small {
+                num_paths += self.count_paths_from(neighbour, small_cave_visits, allow_one_twice);
                 continue;
             }
 
-            for neighbour in self
-                .graph
-                .get(curr_node)
-                .unwrap()
-                .iter()
-                .filter(|n| n.kind() != NodeKind::StartCave)
-            {
-                if neighbour.kind() == NodeKind::SmallCave
-                    && !include_small_cave(&curr_path, Rc::as_ref(neighbour))
-                {
-                    continue;
-                }
+            let num_visits = *small_cave_visits.get(neighbour).unwrap_or(&0);
 
-                let candidate_path = {
-                    let mut v = curr_path.clone();
-                    v.push(neighbour);
-                    v
-                };
-
-                if result.iter().any(|p| {
-                    p.len() == candidate_path.len()
-                        // compare pointers for faster equivalence check
-                        && p[0..candidate_path.len()]
-                            .iter()
-                            .zip(candidate_path[..].iter())
-                            .all(|(&a, &b)| ptr::eq(a, b))
-                }) {
-                    continue;
-                }
+            let allow_one_twice_next = if num_visits == 0 {
+                allow_one_twice
+            } else if allow_one_twice {
+                false
+            } else {
+                continue;
+            };
 
-                visit_paths_next.push(candidate_path);
-            }
+            *small_cave_visits.entry(neighbour).or_insert(0) += 1;
+            num_paths += self.count_paths_from(neighbour, small_cave_visits, allow_one_twice_next);
+            *small_cave_visits.get_mut(neighbour).unwrap() -= 1;
         }
 
+        num_paths
+    }
+
+    #[cfg(test)]
+    fn paths_with_small_caves_once(&self) -> HashSet<Vec<Node>> {
+        self.paths(false)
+    }
+
+    #[cfg(test)]
+    fn paths_with_one_small_cave_twice(&self) -> HashSet<Vec<Node>> {
+        self.paths(true)
+    }
+
+    #[cfg(test)]
+    fn paths(&self, allow_one_twice: bool) -> HashSet<Vec<Node>> {
+        let mut result: HashSet<Vec<Node>> = HashSet::new();
+        self.for_each_path(allow_one_twice, |path| {
+            result.insert(path.iter().map(|&n| n.clone()).collect());
+        });
         result
+    }
+
+    /// Maps path length, in nodes, to the number of paths of that length.
+    fn path_length_histogram(&self, allow_one_twice: bool) -> BTreeMap<usize, usize> {
+        let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+        self.for_each_path(allow_one_twice, |path| {
+            *histogram.entry(path.len()).or_insert(0) += 1;
+        });
+        histogram
+    }
+
+    /// Like `paths_with_small_caves_once`, but allows visiting the small
+    /// cave named `cave` twice.
+    fn paths_allowing_twice(&self, cave: &str) -> Result<HashSet<Vec<Node>>, TwiceCaveError> {
+        let (twice_cave, _) = self
+            .graph
+            .get_key_value(&Node(cave.to_string()))
+            .ok_or_else(|| TwiceCaveError::UnknownCave(cave.to_string()))?;
+
+        if twice_cave.kind() != NodeKind::Small {
+            return Err(TwiceCaveError::NotSmallCave(cave.to_string()));
+        }
+
+        let mut result: HashSet<Vec<Node>> = HashSet::new();
+        self.for_each_path_with(
+            |small_cave_visits, n| {
+                let num_visits = *small_cave_visits.get(n).unwrap_or(&0);
+                num_visits == 0 || (n == Rc::as_ref(twice_cave) && num_visits < 2)
+            },
+            |path| {
+                result.insert(path.iter().map(|&n| n.clone()).collect());
+            },
+        );
+        Ok(result)
+    }
+
+    /// Calls `visit` once for each complete path from the start to the end
+    /// cave, without collecting the paths.
+    fn for_each_path<F: FnMut(&[&Node])>(&self, allow_one_twice: bool, visit: F) {
+        if allow_one_twice {
+            self.for_each_path_with(
+                |small_cave_visits, n| {
+                    *small_cave_visits.get(n).unwrap_or(&0) == 0
+                        || small_cave_visits.values().all(|&num| num < 2)
+                },
+                visit,
+            );
+        } else {
+            self.for_each_path_with(
+                |small_cave_visits, n| *small_cave_visits.get(n).unwrap_or(&0) == 0,
+                visit,
+            );
+        }
+    }
+
+    /// `include_small_cave` decides whether the path may continue to a small
+    /// cave, given the visit counts of the small caves on the path so far.
+    fn for_each_path_with<I, F>(&self, include_small_cave: I, mut visit: F)
+    where
+        I: Fn(&HashMap<&Node, usize>, &Node) -> bool,
+        F: FnMut(&[&Node]),
+    {
+        let mut path: Vec<&Node> = vec![&self.start];
+        let mut small_cave_visits: HashMap<&Node, usize> = HashMap::new();
+        self.for_each_path_from(
+            &mut path,
+            &mut small_cave_visits,
+            &include_small_cave,
+            &mut visit,
+        );
+    }
+
+    fn for_each_path_from<'a, I, F>(
+        &'a self,
+        path: &mut Vec<&'a Node>,
+        small_cave_visits: &mut HashMap<&'a Node, usize>,
+        include_small_cave: &I,
+        visit: &mut F,
+    ) where
+        I: Fn(&HashMap<&Node, usize>, &Node) -> bool,
+        F: FnMut(&[&Node]),
+    {
+        let node = *path.last().unwrap();
+
+        if node == Rc::as_ref(&self.end) {
+            visit(path);
+            return;
+        }
+
+        for neighbour in self
+            .graph
+            .get(node)
+            .unwrap()
             .iter()
-            .map(|p| p.iter().map(|&n| n.clone()).collect())
-            .collect()
+            .filter(|n| n.kind() != NodeKind::Start)
+        {
+            let neighbour = Rc::as_ref(neighbour);
+
+            if neighbour.kind() != NodeKind::Small {
+                path.push(neighbour);
+                self.for_each_path_from(path, small_cave_visits, include_small_cave, visit);
+                path.pop();
+            } else if include_small_cave(small_cave_visits, neighbour) {
+                path.push(neighbour);
+                *small_cave_visits.entry(neighbour).or_insert(0) += 1;
+                self.for_each_path_from(path, small_cave_visits, include_small_cave, visit);
+                *small_cave_visits.get_mut(neighbour).unwrap() -= 1;
+                path.pop();
+            }
+        }
     }
 }
 
@@ -207,7 +382,9 @@ impl TryFrom<Vec<CaveLink>> for CaveGraph {
     }
 }
 
-/// CLI usage: cargo run --release -- input.txt
+/// CLI usage: cargo run --release -- input.txt [cave]
+///
+/// The optional second argument is a small cave that may be visited twice.
 fn main() {
     let filename = env::args().nth(1).expect("Missing input file");
 
@@ -216,20 +393,33 @@ fn main() {
         .map(|l| {
             let line = &l.expect("Line not UTF-8");
             line.parse()
-                .unwrap_or_else(|e| panic!("Invalid edge ({:?}): {}", e, line))
+                .unwrap_or_else(|e| panic!("Invalid edge ({}): {}", e, line))
         })
         .collect::<Vec<CaveLink>>()
         .try_into()
-        .unwrap();
+        .unwrap_or_else(|e| panic!("Invalid cave graph: {}", e));
 
     println!(
         "Number of distinct paths with small caves visited once: {}",
-        map.paths_with_small_caves_once().len(),
+        map.count_paths_small_once(),
     );
 
     println!(
         "  with 1 small cave visited twice: {}",
-        map.paths_with_one_small_cave_twice().len()
+        map.count_paths_one_twice()
+    );
+
+    if let Some(cave) = env::args().nth(2) {
+        let paths = map
+            .paths_allowing_twice(&cave)
+            .unwrap_or_else(|e| panic!("Invalid cave to visit twice: {}", e));
+
+        println!("  with {} visited twice: {}", cave, paths.len());
+    }
+
+    println!(
+        "Number of paths by length, with small caves visited once: {:?}",
+        map.path_length_histogram(false)
     );
 }
 
@@ -237,6 +427,25 @@ fn main() {
 mod tests {
     use super::*;
 
+    const EXAMPLE_SMALL: &str = "start-A\n\
+                                 start-b\n\
+                                 A-c\n\
+                                 A-b\n\
+                                 b-d\n\
+                                 A-end\n\
+                                 b-end";
+
+    const EXAMPLE_MEDIUM: &str = "dc-end\n\
+                                  HN-start\n\
+                                  start-kj\n\
+                                  dc-start\n\
+                                  dc-HN\n\
+                                  LN-dc\n\
+                                  HN-end\n\
+                                  kj-sj\n\
+                                  kj-HN\n\
+                                  kj-dc";
+
     #[test]
     fn collect_paths_with_small_caves_once() {
         let cg: CaveGraph = "start-A\n\
@@ -299,4 +508,130 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn count_paths() {
+        let cg: CaveGraph = EXAMPLE_SMALL.parse().unwrap();
+        assert_eq!(cg.count_paths_small_once(), 10);
+        assert_eq!(cg.count_paths_one_twice(), 36);
+        assert_eq!(
+            cg.count_paths_small_once(),
+            cg.paths_with_small_caves_once().len()
+        );
+        assert_eq!(
+            cg.count_paths_one_twice(),
+            cg.paths_with_one_small_cave_twice().len()
+        );
+    }
+
+    #[test]
+    fn collect_paths_of_medium_example() {
+        let cg: CaveGraph = EXAMPLE_MEDIUM.parse().unwrap();
+        assert_eq!(cg.paths_with_small_caves_once().len(), 19);
+        assert_eq!(cg.paths_with_one_small_cave_twice().len(), 103);
+    }
+
+    #[test]
+    fn count_paths_via_for_each_path() {
+        let cg: CaveGraph = EXAMPLE_MEDIUM.parse().unwrap();
+        let mut num_paths = 0;
+        cg.for_each_path(false, |path| {
+            assert_eq!(path.first().unwrap().0, "start");
+            assert_eq!(path.last().unwrap().0, "end");
+            num_paths += 1;
+        });
+        assert_eq!(num_paths, cg.paths_with_small_caves_once().len());
+    }
+
+    #[test]
+    fn path_length_histogram() {
+        let cg: CaveGraph = EXAMPLE_SMALL.parse().unwrap();
+        assert_eq!(
+            cg.path_length_histogram(false),
+            BTreeMap::from([(3, 2), (4, 2), (5, 2), (6, 2), (7, 2)])
+        );
+        assert_eq!(
+            cg.path_length_histogram(true).values().sum::<usize>(),
+            cg.count_paths_one_twice()
+        );
+    }
+
+    #[test]
+    fn degree_and_reachability() {
+        let cg: CaveGraph = format!("{}\nx-Y", EXAMPLE_SMALL).parse().unwrap();
+        assert_eq!(cg.degree("A"), Some(4));
+        assert_eq!(cg.degree("d"), Some(1));
+        assert_eq!(cg.degree("x"), Some(1));
+        assert_eq!(cg.degree("zz"), None);
+        assert!(cg.is_reachable("start"));
+        assert!(cg.is_reachable("d"));
+        assert!(cg.is_reachable("end"));
+        assert!(!cg.is_reachable("x"));
+        assert!(!cg.is_reachable("Y"));
+        assert!(!cg.is_reachable("zz"));
+    }
+
+    #[test]
+    fn collect_paths_allowing_named_small_cave_twice() {
+        let cg: CaveGraph = EXAMPLE_SMALL.parse().unwrap();
+
+        let paths: HashSet<String> = cg
+            .paths_allowing_twice("b")
+            .unwrap()
+            .iter()
+            .map(|p| p.iter().map(|n| n.0.as_str()).collect::<Vec<_>>().join(","))
+            .collect();
+
+        assert_eq!(
+            paths,
+            HashSet::from(
+                [
+                    "start,A,b,A,b,A,c,A,end",
+                    "start,A,b,A,b,A,end",
+                    "start,A,b,A,b,end",
+                    "start,A,b,A,c,A,b,A,end",
+                    "start,A,b,A,c,A,b,end",
+                    "start,A,b,A,c,A,end",
+                    "start,A,b,A,end",
+                    "start,A,b,d,b,A,c,A,end",
+                    "start,A,b,d,b,A,end",
+                    "start,A,b,d,b,end",
+                    "start,A,b,end",
+                    "start,A,c,A,b,A,b,A,end",
+                    "start,A,c,A,b,A,b,end",
+                    "start,A,c,A,b,A,end",
+                    "start,A,c,A,b,d,b,A,end",
+                    "start,A,c,A,b,d,b,end",
+                    "start,A,c,A,b,end",
+                    "start,A,c,A,end",
+                    "start,A,end",
+                    "start,b,A,b,A,c,A,end",
+                    "start,b,A,b,A,end",
+                    "start,b,A,b,end",
+                    "start,b,A,c,A,b,A,end",
+                    "start,b,A,c,A,b,end",
+                    "start,b,A,c,A,end",
+                    "start,b,A,end",
+                    "start,b,d,b,A,c,A,end",
+                    "start,b,d,b,A,end",
+                    "start,b,d,b,end",
+                    "start,b,end",
+                ]
+                .map(String::from)
+            )
+        );
+
+        assert_eq!(
+            cg.paths_allowing_twice("A").unwrap_err(),
+            TwiceCaveError::NotSmallCave("A".to_string())
+        );
+        assert_eq!(
+            cg.paths_allowing_twice("start").unwrap_err(),
+            TwiceCaveError::NotSmallCave("start".to_string())
+        );
+        assert_eq!(
+            cg.paths_allowing_twice("zz").unwrap_err().to_string(),
+            "Unknown cave: zz"
+        );
+    }
 }